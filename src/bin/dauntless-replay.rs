@@ -0,0 +1,375 @@
+use dauntless_srv::calib::{Calibration, Remap};
+use dauntless_srv::config::{Config, Filter, Undistort};
+use dauntless_srv::data::{self, CameraTag};
+use dauntless_srv::geom::{Intrinsics, Rotation};
+use dauntless_srv::record::{self, Entry, Record};
+use dauntless_srv::track::Tracker;
+
+use dauntless::Detector;
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+use std::time::Instant;
+
+use anyhow::{anyhow, bail, Result};
+use colored::Colorize;
+use serde::Serialize;
+use serde_json::Value;
+
+/// Pose deltas below these are treated as unchanged when filtering frames.
+const EPSILON_M: f64 = 1e-4;
+const EPSILON_DEG: f64 = 1e-2;
+
+const USAGE: &str = "\
+usage: dauntless-replay <session|segment>... [options]
+
+options:
+  --config <file>     merge a partial config json over the recorded one
+  --set <path>=<val>  override one config value, e.g. detector.hyst_high=0.3
+  --camera <id>       only replay one camera
+  --all               report every frame, not just ones that changed
+  --json              print one json object per frame";
+
+struct Args {
+    paths: Vec<PathBuf>,
+    patch: Value,
+    camera: Option<u32>,
+    all: bool,
+    json: bool,
+}
+
+struct Camera {
+    config: Config,
+    filter: Filter,
+    calibration: Option<Calibration>,
+    remap: Option<Remap>,
+    detector: Detector,
+    tracker: Tracker,
+}
+
+#[derive(Serialize)]
+struct Delta {
+    id: u32,
+    translation: f64,
+    rotation: f64,
+}
+
+impl Delta {
+    fn moved(&self) -> bool {
+        self.translation > EPSILON_M || self.rotation > EPSILON_DEG
+    }
+}
+
+#[derive(Serialize)]
+struct FrameDiff {
+    camera: u32,
+    seq: u64,
+    time: f64,
+    recorded_ms: f32,
+    replayed_ms: f32,
+    gained: Vec<u32>,
+    lost: Vec<u32>,
+    deltas: Vec<Delta>,
+}
+
+impl FrameDiff {
+    fn changed(&self) -> bool {
+        !self.gained.is_empty()
+            || !self.lost.is_empty()
+            || self.deltas.iter().any(Delta::moved)
+    }
+}
+
+#[derive(Default)]
+struct Summary {
+    frames: u64,
+    changed: u64,
+    gained: u64,
+    lost: u64,
+    recorded_ms: f64,
+    replayed_ms: f64,
+    deltas: u64,
+    translation: f64,
+    max_translation: f64,
+}
+
+fn main() {
+    let args = match parse() {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("replay: {} [reason: {}]\n\n{}", "bad arguments".red(), err, USAGE);
+            process::exit(2);
+        }
+    };
+
+    if let Err(err) = run(&args) {
+        eprintln!("replay: {} [reason: {}]", "failed".red(), err);
+        process::exit(1);
+    }
+}
+
+fn parse() -> Result<Args> {
+    let mut args = Args { paths: Vec::new(), patch: Value::Object(Default::default()), camera: None, all: false, json: false };
+    let mut iter = env::args().skip(1);
+
+    while let Some(arg) = iter.next() {
+        let mut value = || iter.next().ok_or_else(|| anyhow!("{} needs a value", arg));
+
+        match arg.as_str() {
+            "--config" => {
+                let patch: Value = serde_json::from_str(&fs::read_to_string(value()?)?)?;
+                merge(&mut args.patch, patch);
+            }
+            "--set" => {
+                let set = value()?;
+                let (path, val) = set.split_once('=').ok_or_else(|| anyhow!("expected <path>=<value>"))?;
+
+                let val = serde_json::from_str(val).unwrap_or_else(|_| Value::String(val.into()));
+                let patch = path.rsplit('.').fold(val, |v, key| Value::Object([(key.to_string(), v)].into_iter().collect()));
+
+                merge(&mut args.patch, patch);
+            }
+            "--camera" => args.camera = Some(value()?.parse()?),
+            "--all" => args.all = true,
+            "--json" => args.json = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ if arg.starts_with("--") => bail!("unknown option {}", arg),
+            _ => args.paths.push(arg.into()),
+        }
+    }
+
+    if args.paths.is_empty() {
+        bail!("no recordings given");
+    }
+
+    Ok(args)
+}
+
+fn run(args: &Args) -> Result<()> {
+    let mut cameras: HashMap<u32, Camera> = HashMap::new();
+    let mut summary = Summary::default();
+
+    for path in &args.paths {
+        for segment in record::segments(path)? {
+            for entry in record::Reader::open(&segment)? {
+                match entry? {
+                    Entry::Config { camera, config, filter, calibration } => {
+                        let config = patch(*config, &args.patch)?;
+
+                        let cam = cameras.entry(camera).or_insert_with(|| Camera {
+                            config: config.clone(),
+                            filter: filter.clone(),
+                            calibration: None,
+                            remap: None,
+                            detector: Detector::new(),
+                            tracker: Tracker::default(),
+                        });
+
+                        cam.config = config;
+                        cam.filter = filter;
+                        cam.calibration = calibration;
+                    }
+                    Entry::Frame(rec) => {
+                        if args.camera.is_some_and(|c| c != rec.camera) {
+                            continue;
+                        }
+
+                        let Some(cam) = cameras.get_mut(&rec.camera) else {
+                            bail!("{}: frame before any config", segment.display());
+                        };
+
+                        let diff = replay(cam, &rec)?;
+                        summary.add(&diff);
+
+                        if args.json {
+                            println!("{}", serde_json::to_string(&diff)?);
+                        } else if args.all || diff.changed() {
+                            print(&diff);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    if !args.json {
+        summary.print();
+    }
+
+    Ok(())
+}
+
+/// Runs a recorded frame back through the detector and pose solve the way
+/// the server would have, then compares it to what was recorded.
+fn replay(cam: &mut Camera, rec: &Record) -> Result<FrameDiff> {
+    let (w, h) = (rec.width, rec.height);
+    let config = &cam.config;
+
+    let mut img: Vec<f32> = record::decompress(rec)?.iter().map(|&v| v as f32 / 255.0).collect();
+
+    let calib = cam.calibration.clone().filter(|c| c.res == (w, h));
+
+    if let (Undistort::Frame, Some(calib)) = (config.server.undistort, &calib) {
+        let table = match cam.remap.take() {
+            Some(r) if r.calib == *calib => r,
+            _ => Remap::new(calib.clone()),
+        };
+
+        let mut out = vec![0.0; img.len()];
+        table.apply(&img, &mut out);
+        img = out;
+
+        cam.remap = Some(table);
+    }
+
+    let start = Instant::now();
+    let (tags, _) = cam.detector.process(w as usize, h as usize, &config.detector, &img);
+    let ms = start.elapsed().as_secs_f32() * 1000.0;
+
+    let intr =
+        calib
+            .as_ref()
+            .map(|c| c.intrinsics())
+            .unwrap_or_else(|| Intrinsics::from_fov(w, h, config.detector.fov as f64));
+
+    let corner_calib = calib.filter(|_| config.server.undistort == Undistort::Corners);
+
+    let accepted: Vec<CameraTag> =
+        data::solve_tags(rec.camera, config, intr, corner_calib.as_ref(), rec.time, &tags)
+            .into_iter()
+            .filter(|t| config.filter.accepts(t) && cam.filter.accepts(t))
+            .collect();
+
+    let accepted =
+        if config.track.enabled {
+            cam.tracker.update(&config.track, config.mount.transform(), rec.time, &accepted)
+        } else {
+            accepted
+        };
+
+    Ok(diff(rec, ms, &accepted))
+}
+
+fn diff(rec: &Record, ms: f32, replayed: &[CameraTag]) -> FrameDiff {
+    let by_id = |tags: &[CameraTag]| -> HashMap<u32, CameraTag> {
+        tags.iter().filter_map(|t| t.tag.id.map(|id| (id, *t))).collect()
+    };
+
+    let (before, after) = (by_id(&rec.tags), by_id(replayed));
+
+    let mut gained: Vec<u32> = after.keys().filter(|id| !before.contains_key(id)).copied().collect();
+    let mut lost: Vec<u32> = before.keys().filter(|id| !after.contains_key(id)).copied().collect();
+
+    let mut deltas: Vec<Delta> =
+        before
+            .iter()
+            .filter_map(|(&id, old)| {
+                let new = after.get(&id)?;
+
+                Some(Delta {
+                    id,
+                    translation: (new.poses.best.translation - old.poses.best.translation).norm(),
+                    rotation: angle(old.poses.best.rotation, new.poses.best.rotation).to_degrees(),
+                })
+            })
+            .collect();
+
+    gained.sort();
+    lost.sort();
+    deltas.sort_by_key(|d| d.id);
+
+    FrameDiff {
+        camera: rec.camera,
+        seq: rec.seq,
+        time: rec.time,
+        recorded_ms: rec.ms,
+        replayed_ms: ms,
+        gained,
+        lost,
+        deltas,
+    }
+}
+
+fn angle(a: Rotation, b: Rotation) -> f64 {
+    2.0 * (a.inverse() * b).w.abs().min(1.0).acos()
+}
+
+fn print(diff: &FrameDiff) {
+    let mut line = format!(
+        "cam{} #{:<6} {:.3}  {:>6.2}ms -> {:>6.2}ms",
+        diff.camera, diff.seq, diff.time, diff.recorded_ms, diff.replayed_ms,
+    );
+
+    if !diff.gained.is_empty() {
+        line += &format!("  {}", format!("+{:?}", diff.gained).green());
+    }
+    if !diff.lost.is_empty() {
+        line += &format!("  {}", format!("-{:?}", diff.lost).red());
+    }
+
+    for d in diff.deltas.iter().filter(|d| d.moved()) {
+        line += &format!("  {}: {:.3}m {:.2}°", d.id, d.translation, d.rotation);
+    }
+
+    println!("{}", line);
+}
+
+impl Summary {
+    fn add(&mut self, diff: &FrameDiff) {
+        self.frames += 1;
+        self.changed += diff.changed() as u64;
+        self.gained += diff.gained.len() as u64;
+        self.lost += diff.lost.len() as u64;
+        self.recorded_ms += diff.recorded_ms as f64;
+        self.replayed_ms += diff.replayed_ms as f64;
+
+        for d in &diff.deltas {
+            self.deltas += 1;
+            self.translation += d.translation;
+            self.max_translation = self.max_translation.max(d.translation);
+        }
+    }
+
+    fn print(&self) {
+        let n = self.frames.max(1) as f64;
+
+        println!(
+            "\nreplay: {} [frames: {}, changed: {}, gained: {}, lost: {}]",
+            "done".green(), self.frames, self.changed, self.gained, self.lost,
+        );
+        println!(
+            "replay: timing [recorded: {:.2}ms, replayed: {:.2}ms]",
+            self.recorded_ms / n, self.replayed_ms / n,
+        );
+        println!(
+            "replay: pose delta [mean: {:.4}m, max: {:.4}m]",
+            self.translation / self.deltas.max(1) as f64, self.max_translation,
+        );
+    }
+}
+
+/// Applies `patch` over the serialized config, so partial overrides work
+/// for any field.
+fn patch(config: Config, patch: &Value) -> Result<Config> {
+    let mut value = serde_json::to_value(config)?;
+    merge(&mut value, patch.clone());
+
+    Ok(serde_json::from_value(value)?)
+}
+
+fn merge(base: &mut Value, patch: Value) {
+    match (base, patch) {
+        (Value::Object(base), Value::Object(patch)) => {
+            for (key, val) in patch {
+                merge(base.entry(key).or_insert(Value::Null), val);
+            }
+        }
+        (base, patch) => *base = patch,
+    }
+}
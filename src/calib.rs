@@ -0,0 +1,700 @@
+use crate::config;
+use crate::geom::{Intrinsics, Rotation, Transform, Translation};
+use crate::solve;
+
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+
+const MIN_VIEWS: usize = 3;
+const MAX_CANDIDATES: usize = 600;
+const MAX_SEEDS: usize = 40;
+
+/// Calibration targets the detector can find. ChArUco boards are not
+/// supported yet; print a plain chessboard instead.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BoardKind {
+    Chessboard,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Board {
+    pub kind: BoardKind,
+    pub cols: u32,
+    pub rows: u32,
+    pub square: f64,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Calibration {
+    pub camera: u32,
+    pub res: (u32, u32),
+    pub fx: f64,
+    pub fy: f64,
+    pub cx: f64,
+    pub cy: f64,
+    pub dist: [f64; 5],
+    pub error: f64,
+    pub views: Vec<f64>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct Session {
+    pub board: Board,
+    pub res: (u32, u32),
+    pub views: Vec<Vec<(f64, f64)>>,
+    pub last: Option<String>,
+    pub result: Option<Calibration>,
+}
+
+impl Board {
+    pub fn validate(&self) -> Result<()> {
+        if self.cols < 3 || self.rows < 3 || self.cols == self.rows {
+            bail!("board needs at least 3x3 inner corners and cols != rows");
+        }
+        if self.square <= 0.0 {
+            bail!("square size must be positive");
+        }
+
+        Ok(())
+    }
+
+    fn points(&self) -> Vec<Translation> {
+        (0..self.rows)
+            .flat_map(|j| (0..self.cols).map(move |i| (i, j)))
+            .map(|(i, j)| Translation::new(i as f64 * self.square, j as f64 * self.square, 0.0))
+            .collect()
+    }
+}
+
+impl Calibration {
+    pub fn intrinsics(&self) -> Intrinsics {
+        Intrinsics { fx: self.fx, fy: self.fy, cx: self.cx, cy: self.cy }
+    }
+
+    pub fn load_all() -> Result<Vec<Self>> {
+        let file = File::open(config::path("calibration.json"))?;
+        Ok(serde_json::from_reader(BufReader::new(file))?)
+    }
+
+    pub fn save_all(calibrations: &[Self]) -> Result<()> {
+        let file = File::create(config::path("calibration.json"))?;
+        serde_json::to_writer_pretty(BufWriter::new(file), calibrations)?;
+        Ok(())
+    }
+
+    /// Projects normalized image coordinates through the lens model to a pixel.
+    pub fn distort(&self, p: (f64, f64)) -> (f64, f64) {
+        distort(self.fx, self.fy, self.cx, self.cy, self.dist, p)
+    }
+
+    /// Removes lens distortion from a pixel by fixed-point iteration.
+    pub fn undistort(&self, (u, v): (f64, f64)) -> (f64, f64) {
+        let [k1, k2, p1, p2, k3] = self.dist;
+
+        let (xd, yd) = ((u - self.cx) / self.fx, (v - self.cy) / self.fy);
+        let (mut x, mut y) = (xd, yd);
+
+        for _ in 0..10 {
+            let r2 = x * x + y * y;
+            let radial = 1.0 + k1 * r2 + k2 * r2 * r2 + k3 * r2 * r2 * r2;
+
+            let dx = 2.0 * p1 * x * y + p2 * (r2 + 2.0 * x * x);
+            let dy = p1 * (r2 + 2.0 * y * y) + 2.0 * p2 * x * y;
+
+            x = (xd - dx) / radial;
+            y = (yd - dy) / radial;
+        }
+
+        (self.fx * x + self.cx, self.fy * y + self.cy)
+    }
+}
+
+/// Precomputed bilinear lookup from undistorted output pixels to the raw frame.
+pub struct Remap {
+    pub calib: Calibration,
+    table: Vec<(u32, f32, f32)>,
+}
+
+impl Remap {
+    pub fn new(calib: Calibration) -> Self {
+        let (w, h) = calib.res;
+
+        let table =
+            (0..h)
+                .flat_map(|v| (0..w).map(move |u| (u, v)))
+                .map(|(u, v)| {
+                    let x = (u as f64 - calib.cx) / calib.fx;
+                    let y = (v as f64 - calib.cy) / calib.fy;
+                    let (su, sv) = calib.distort((x, y));
+
+                    if su < 0.0 || sv < 0.0 || su >= (w - 1) as f64 || sv >= (h - 1) as f64 {
+                        return (u32::MAX, 0.0, 0.0);
+                    }
+
+                    let (iu, iv) = (su.floor(), sv.floor());
+                    ((iv as u32) * w + iu as u32, (su - iu) as f32, (sv - iv) as f32)
+                })
+                .collect();
+
+        Self { calib, table }
+    }
+
+    pub fn apply(&self, src: &[f32], dst: &mut [f32]) {
+        let w = self.calib.res.0 as usize;
+
+        for (out, &(i, fx, fy)) in dst.iter_mut().zip(&self.table) {
+            if i == u32::MAX {
+                *out = 0.0;
+                continue;
+            }
+
+            let i = i as usize;
+            let top = src[i] * (1.0 - fx) + src[i + 1] * fx;
+            let bottom = src[i + w] * (1.0 - fx) + src[i + w + 1] * fx;
+
+            *out = top * (1.0 - fy) + bottom * fy;
+        }
+    }
+}
+
+impl Session {
+    pub fn new(board: Board, res: (u32, u32)) -> Self {
+        Self { board, res, views: Vec::new(), last: None, result: None }
+    }
+
+    /// Detects the board in a full-resolution luma frame and keeps the view
+    /// if it is complete and distinct from the views already captured.
+    pub fn capture(&mut self, w: u32, h: u32, img: &[f32]) {
+        if (w, h) != self.res {
+            self.last = Some("resolution changed, restart the session".into());
+            return;
+        }
+
+        let corners = match detect(w as usize, h as usize, img, &self.board) {
+            Ok(corners) => corners,
+            Err(err) => {
+                self.last = Some(err.to_string());
+                return;
+            }
+        };
+
+        let min_shift = w as f64 * 0.02;
+        let similar =
+            self.views
+                .iter()
+                .position(|view| {
+                    let shift: f64 =
+                        view.iter()
+                            .zip(&corners)
+                            .map(|(a, b)| ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt())
+                            .sum();
+
+                    shift / (corners.len() as f64) < min_shift
+                });
+
+        self.last = Some(match similar {
+            Some(i) => format!("too similar to view {}", i),
+            None => {
+                self.views.push(corners);
+                format!("captured view {}", self.views.len() - 1)
+            }
+        });
+    }
+
+    pub fn solve(&mut self, camera: u32) -> Result<Calibration> {
+        if self.views.len() < MIN_VIEWS {
+            bail!("need at least {} views, have {}", MIN_VIEWS, self.views.len());
+        }
+
+        let calibration = calibrate(camera, self.res, &self.board, &self.views)?;
+        self.result = Some(calibration.clone());
+
+        Ok(calibration)
+    }
+}
+
+fn distort(fx: f64, fy: f64, cx: f64, cy: f64, [k1, k2, p1, p2, k3]: [f64; 5], (x, y): (f64, f64)) -> (f64, f64) {
+    let r2 = x * x + y * y;
+    let radial = 1.0 + k1 * r2 + k2 * r2 * r2 + k3 * r2 * r2 * r2;
+
+    let xd = x * radial + 2.0 * p1 * x * y + p2 * (r2 + 2.0 * x * x);
+    let yd = y * radial + p1 * (r2 + 2.0 * y * y) + 2.0 * p2 * x * y;
+
+    (fx * xd + cx, fy * yd + cy)
+}
+
+/// Finds the inner corners of a chessboard, ordered row by row.
+pub fn detect(w: usize, h: usize, img: &[f32], board: &Board) -> Result<Vec<(f64, f64)>> {
+    let blurred = blur(w, h, img, 1.5);
+    let candidates = saddles(w, h, &blurred);
+
+    let (cols, rows) = (board.cols as i32, board.rows as i32);
+
+    for seed in 0..candidates.len().min(MAX_SEEDS) {
+        let Some(grid) = grow(&candidates, seed) else {
+            continue;
+        };
+
+        let (min_i, max_i) = bounds(grid.keys().map(|k| k.0));
+        let (min_j, max_j) = bounds(grid.keys().map(|k| k.1));
+        let (nx, ny) = (max_i - min_i + 1, max_j - min_j + 1);
+
+        if grid.len() as i32 != cols * rows {
+            continue;
+        }
+
+        let transpose =
+            match (nx, ny) {
+                (x, y) if (x, y) == (cols, rows) => false,
+                (x, y) if (x, y) == (rows, cols) => true,
+                _ => continue,
+            };
+
+        let at = |i: i32, j: i32| {
+            let (gi, gj) = if transpose { (min_i + j, min_j + i) } else { (min_i + i, min_j + j) };
+            candidates[grid[&(gi, gj)]]
+        };
+
+        let origin = at(0, 0);
+        let (a, b) = (at(1, 0), at(0, 1));
+        let cross = (a.0 - origin.0) * (b.1 - origin.1) - (a.1 - origin.1) * (b.0 - origin.0);
+
+        let corners =
+            (0..rows)
+                .flat_map(|j| (0..cols).map(move |i| (i, j)))
+                .map(|(i, j)| if cross > 0.0 { at(i, j) } else { at(i, rows - 1 - j) })
+                .collect();
+
+        return Ok(corners);
+    }
+
+    Err(anyhow!("board not found [{} candidate corners]", candidates.len()))
+}
+
+pub(crate) fn blur(w: usize, h: usize, img: &[f32], sigma: f64) -> Vec<f64> {
+    let r = (sigma * 3.0).ceil() as isize;
+
+    let knl: Vec<f64> = (-r..=r).map(|i| (-(i * i) as f64 / (2.0 * sigma * sigma)).exp()).collect();
+    let sum: f64 = knl.iter().sum();
+    let knl: Vec<f64> = knl.iter().map(|k| k / sum).collect();
+
+    let mut tmp = vec![0.0; w * h];
+    let mut out = vec![0.0; w * h];
+
+    for y in 0..h {
+        for x in 0..w {
+            tmp[y * w + x] =
+                knl.iter()
+                    .enumerate()
+                    .map(|(k, kv)| {
+                        let xx = (x as isize + k as isize - r).clamp(0, w as isize - 1) as usize;
+                        img[y * w + xx] as f64 * kv
+                    })
+                    .sum();
+        }
+    }
+
+    for y in 0..h {
+        for x in 0..w {
+            out[y * w + x] =
+                knl.iter()
+                    .enumerate()
+                    .map(|(k, kv)| {
+                        let yy = (y as isize + k as isize - r).clamp(0, h as isize - 1) as usize;
+                        tmp[yy * w + x] * kv
+                    })
+                    .sum();
+        }
+    }
+
+    out
+}
+
+/// Finds saddle points from the Hessian determinant, refined to subpixel
+/// precision with a Newton step on the image gradient.
+fn saddles(w: usize, h: usize, img: &[f64]) -> Vec<(f64, f64)> {
+    const R: usize = 4;
+
+    let at = |x: usize, y: usize| img[y * w + x];
+    let hessian = |x: usize, y: usize| {
+        let xx = at(x + 1, y) - 2.0 * at(x, y) + at(x - 1, y);
+        let yy = at(x, y + 1) - 2.0 * at(x, y) + at(x, y - 1);
+        let xy = (at(x + 1, y + 1) - at(x + 1, y - 1) - at(x - 1, y + 1) + at(x - 1, y - 1)) / 4.0;
+        (xx, yy, xy)
+    };
+
+    if w <= 2 * R + 2 || h <= 2 * R + 2 {
+        return Vec::new();
+    }
+
+    let mut resp = vec![0.0; w * h];
+    for y in 1..h - 1 {
+        for x in 1..w - 1 {
+            let (xx, yy, xy) = hessian(x, y);
+            resp[y * w + x] = (xy * xy - xx * yy).max(0.0);
+        }
+    }
+
+    let max = resp.iter().cloned().fold(0.0, f64::max);
+    let thresh = max * 0.05;
+
+    let mut peaks: Vec<(f64, (f64, f64))> = Vec::new();
+
+    for y in R + 1..h - R - 1 {
+        for x in R + 1..w - R - 1 {
+            let v = resp[y * w + x];
+            if v <= thresh {
+                continue;
+            }
+
+            let is_max =
+                (y - R..=y + R).all(|yy| (x - R..=x + R).all(|xx| {
+                    let o = resp[yy * w + xx];
+                    o < v || (o == v && (yy, xx) >= (y, x))
+                }));
+            if !is_max {
+                continue;
+            }
+
+            if !crossing(w, img, x, y, R as f64) {
+                continue;
+            }
+
+            let (xx, yy, xy) = hessian(x, y);
+            let gx = (at(x + 1, y) - at(x - 1, y)) / 2.0;
+            let gy = (at(x, y + 1) - at(x, y - 1)) / 2.0;
+
+            let det = xx * yy - xy * xy;
+            let (dx, dy) =
+                if det.abs() > 1e-12 {
+                    (-(yy * gx - xy * gy) / det, -(xx * gy - xy * gx) / det)
+                } else {
+                    (0.0, 0.0)
+                };
+
+            peaks.push((v, (x as f64 + dx.clamp(-1.0, 1.0), y as f64 + dy.clamp(-1.0, 1.0))));
+        }
+    }
+
+    peaks.sort_by(|a, b| b.0.total_cmp(&a.0));
+    peaks.into_iter().take(MAX_CANDIDATES).map(|(_, p)| p).collect()
+}
+
+/// Checks that a ring around the point alternates dark and light four times,
+/// which rejects edges and T-junctions at the board border.
+fn crossing(w: usize, img: &[f64], x: usize, y: usize, r: f64) -> bool {
+    const N: usize = 16;
+
+    let ring: Vec<f64> =
+        (0..N)
+            .map(|k| {
+                let a = k as f64 / N as f64 * std::f64::consts::TAU;
+                let (sx, sy) = ((x as f64 + r * a.cos()).round(), (y as f64 + r * a.sin()).round());
+                img[sy as usize * w + sx as usize]
+            })
+            .collect();
+
+    let (lo, hi) = ring.iter().fold((f64::MAX, f64::MIN), |(lo, hi), v| (lo.min(*v), hi.max(*v)));
+    let mid = (lo + hi) / 2.0;
+
+    let changes = (0..N).filter(|&k| (ring[k] > mid) != (ring[(k + 1) % N] > mid)).count();
+    changes == 4
+}
+
+/// Grows a lattice of grid coordinates outward from a seed corner.
+fn grow(pts: &[(f64, f64)], seed: usize) -> Option<HashMap<(i32, i32), usize>> {
+    let dist = |a: (f64, f64), b: (f64, f64)| ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt();
+    let sub = |a: (f64, f64), b: (f64, f64)| (a.0 - b.0, a.1 - b.1);
+
+    let mut near: Vec<usize> = (0..pts.len()).filter(|&i| i != seed).collect();
+    near.sort_by(|&a, &b| dist(pts[a], pts[seed]).total_cmp(&dist(pts[b], pts[seed])));
+
+    let first = *near.first()?;
+    let u = sub(pts[first], pts[seed]);
+    let ul = dist(pts[first], pts[seed]);
+
+    let second = near.iter().take(8).skip(1).copied().find(|&i| {
+        let v = sub(pts[i], pts[seed]);
+        let cos = (u.0 * v.0 + u.1 * v.1) / (ul * dist(pts[i], pts[seed]));
+        cos.abs() < 0.5
+    })?;
+    let v = sub(pts[second], pts[seed]);
+
+    let mut grid = HashMap::from([((0, 0), seed), ((1, 0), first), ((0, 1), second)]);
+    let mut used: Vec<bool> = vec![false; pts.len()];
+    for &i in grid.values() {
+        used[i] = true;
+    }
+
+    let mut queue: VecDeque<(i32, i32)> = grid.keys().copied().collect();
+
+    while let Some((i, j)) = queue.pop_front() {
+        let p = pts[grid[&(i, j)]];
+
+        for (di, dj) in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+            let cell = (i + di, j + dj);
+            if grid.contains_key(&cell) {
+                continue;
+            }
+
+            let pred =
+                match grid.get(&(i - di, j - dj)) {
+                    Some(&back) => (2.0 * p.0 - pts[back].0, 2.0 * p.1 - pts[back].1),
+                    None => (
+                        p.0 + di as f64 * u.0 + dj as f64 * v.0,
+                        p.1 + di as f64 * u.1 + dj as f64 * v.1,
+                    ),
+                };
+            let tol = dist(pred, p) * 0.35;
+
+            let found =
+                (0..pts.len())
+                    .filter(|&k| !used[k])
+                    .map(|k| (k, dist(pts[k], pred)))
+                    .filter(|(_, d)| *d < tol)
+                    .min_by(|a, b| a.1.total_cmp(&b.1));
+
+            if let Some((k, _)) = found {
+                used[k] = true;
+                grid.insert(cell, k);
+                queue.push_back(cell);
+            }
+        }
+    }
+
+    Some(grid)
+}
+
+fn bounds(vals: impl Iterator<Item = i32>) -> (i32, i32) {
+    vals.fold((i32::MAX, i32::MIN), |(lo, hi), v| (lo.min(v), hi.max(v)))
+}
+
+/// Zhang's closed-form initialization followed by a joint refinement of
+/// intrinsics, distortion and every view's board pose.
+fn calibrate(camera: u32, res: (u32, u32), board: &Board, views: &[Vec<(f64, f64)>]) -> Result<Calibration> {
+    let obj = board.points();
+
+    let homographies: Vec<[[f64; 3]; 3]> = views.iter().map(|v| homography(&obj, v)).collect();
+    let (fx, fy, cx, cy) = zhang(&homographies).ok_or_else(|| anyhow!("degenerate views, tilt the board more"))?;
+
+    let poses: Vec<Transform> = homographies.iter().map(|h| extrinsics(h, fx, fy, cx, cy)).collect();
+
+    let mut x0 = vec![fx, fy, cx, cy, 0.0, 0.0, 0.0, 0.0, 0.0];
+    x0.extend(std::iter::repeat_n(0.0, 6 * views.len()));
+
+    let residuals = |x: &[f64]| -> Vec<f64> {
+        let dist = [x[4], x[5], x[6], x[7], x[8]];
+
+        views
+            .iter()
+            .enumerate()
+            .flat_map(|(i, view)| {
+                let pose = solve::perturb(poses[i], &x[9 + i * 6..15 + i * 6]);
+                reproject(x[0], x[1], x[2], x[3], dist, pose, &obj, view)
+            })
+            .collect()
+    };
+
+    let (x, _) = solve::lm(x0, residuals).ok_or_else(|| anyhow!("refinement diverged"))?;
+    let dist = [x[4], x[5], x[6], x[7], x[8]];
+
+    let errors: Vec<f64> =
+        views
+            .iter()
+            .enumerate()
+            .map(|(i, view)| {
+                let pose = solve::perturb(poses[i], &x[9 + i * 6..15 + i * 6]);
+                let res = reproject(x[0], x[1], x[2], x[3], dist, pose, &obj, view);
+                (res.iter().map(|r| r * r).sum::<f64>() / view.len() as f64).sqrt()
+            })
+            .collect();
+
+    let error = (errors.iter().map(|e| e * e).sum::<f64>() / errors.len() as f64).sqrt();
+
+    Ok(Calibration { camera, res, fx: x[0], fy: x[1], cx: x[2], cy: x[3], dist, error, views: errors })
+}
+
+#[allow(clippy::too_many_arguments)]
+fn reproject(
+    fx: f64,
+    fy: f64,
+    cx: f64,
+    cy: f64,
+    dist: [f64; 5],
+    pose: Transform,
+    obj: &[Translation],
+    view: &[(f64, f64)],
+) -> Vec<f64> {
+    obj.iter()
+        .zip(view)
+        .flat_map(|(p, (u, v))| {
+            let c = pose.apply(*p);
+            if c.z <= 1e-6 {
+                return [1e4, 1e4];
+            }
+
+            let (pu, pv) = distort(fx, fy, cx, cy, dist, (c.x / c.z, c.y / c.z));
+            [pu - u, pv - v]
+        })
+        .collect()
+}
+
+/// Normalized DLT homography from board plane points to pixels.
+fn homography(obj: &[Translation], img: &[(f64, f64)]) -> [[f64; 3]; 3] {
+    let norm = |pts: &[(f64, f64)]| {
+        let n = pts.len() as f64;
+        let (mx, my) = (pts.iter().map(|p| p.0).sum::<f64>() / n, pts.iter().map(|p| p.1).sum::<f64>() / n);
+        let d = pts.iter().map(|p| ((p.0 - mx).powi(2) + (p.1 - my).powi(2)).sqrt()).sum::<f64>() / n;
+        let s = 2f64.sqrt() / d.max(1e-12);
+        [[s, 0.0, -s * mx], [0.0, s, -s * my], [0.0, 0.0, 1.0]]
+    };
+    let apply = |t: &[[f64; 3]; 3], p: (f64, f64)| (t[0][0] * p.0 + t[0][2], t[1][1] * p.1 + t[1][2]);
+
+    let obj2: Vec<(f64, f64)> = obj.iter().map(|p| (p.x, p.y)).collect();
+    let (to, ti) = (norm(&obj2), norm(img));
+
+    let mut ata = vec![vec![0.0; 9]; 9];
+    for (o, i) in obj2.iter().zip(img) {
+        let (x, y) = apply(&to, *o);
+        let (u, v) = apply(&ti, *i);
+
+        for row in [
+            [-x, -y, -1.0, 0.0, 0.0, 0.0, u * x, u * y, u],
+            [0.0, 0.0, 0.0, -x, -y, -1.0, v * x, v * y, v],
+        ] {
+            for a in 0..9 {
+                for b in 0..9 {
+                    ata[a][b] += row[a] * row[b];
+                }
+            }
+        }
+    }
+
+    let h = solve::smallest_eigenvector(ata);
+    let hn = [[h[0], h[1], h[2]], [h[3], h[4], h[5]], [h[6], h[7], h[8]]];
+
+    let ti_inv = [[1.0 / ti[0][0], 0.0, -ti[0][2] / ti[0][0]], [0.0, 1.0 / ti[1][1], -ti[1][2] / ti[1][1]], [0.0, 0.0, 1.0]];
+    mul(&mul(&ti_inv, &hn), &to)
+}
+
+fn zhang(hs: &[[[f64; 3]; 3]]) -> Option<(f64, f64, f64, f64)> {
+    let v = |h: &[[f64; 3]; 3], i: usize, j: usize| {
+        [
+            h[0][i] * h[0][j],
+            h[0][i] * h[1][j] + h[1][i] * h[0][j],
+            h[1][i] * h[1][j],
+            h[2][i] * h[0][j] + h[0][i] * h[2][j],
+            h[2][i] * h[1][j] + h[1][i] * h[2][j],
+            h[2][i] * h[2][j],
+        ]
+    };
+
+    let mut vtv = vec![vec![0.0; 6]; 6];
+    for h in hs {
+        let (v12, v11, v22) = (v(h, 0, 1), v(h, 0, 0), v(h, 1, 1));
+        let diff: Vec<f64> = v11.iter().zip(&v22).map(|(a, b)| a - b).collect();
+
+        for row in [v12.to_vec(), diff] {
+            for a in 0..6 {
+                for b in 0..6 {
+                    vtv[a][b] += row[a] * row[b];
+                }
+            }
+        }
+    }
+
+    let mut b = solve::smallest_eigenvector(vtv);
+    if b[0] < 0.0 {
+        b.iter_mut().for_each(|x| *x = -*x);
+    }
+    let [b11, b12, b22, b13, b23, b33] = [b[0], b[1], b[2], b[3], b[4], b[5]];
+
+    let den = b11 * b22 - b12 * b12;
+    let cy = (b12 * b13 - b11 * b23) / den;
+    let lambda = b33 - (b13 * b13 + cy * (b12 * b13 - b11 * b23)) / b11;
+
+    let fx = (lambda / b11).sqrt();
+    let fy = (lambda * b11 / den).sqrt();
+    let cx = -b13 * fx * fx / lambda;
+
+    [fx, fy, cx, cy].iter().all(|v| v.is_finite() && *v > 0.0).then_some((fx, fy, cx, cy))
+}
+
+fn extrinsics(h: &[[f64; 3]; 3], fx: f64, fy: f64, cx: f64, cy: f64) -> Transform {
+    let kinv = |c: [f64; 3]| [(c[0] - cx * c[2]) / fx, (c[1] - cy * c[2]) / fy, c[2]];
+    let col = |i: usize| {
+        let [x, y, z] = kinv([h[0][i], h[1][i], h[2][i]]);
+        Translation::new(x, y, z)
+    };
+
+    let (h1, h2, h3) = (col(0), col(1), col(2));
+    let mut lambda = 1.0 / h1.norm();
+    if h3.z * lambda < 0.0 {
+        lambda = -lambda;
+    }
+
+    let r1 = h1.scale(lambda);
+    let r1 = r1.scale(1.0 / r1.norm());
+    let r2 = h2.scale(lambda);
+    let r2 = r2 - r1.scale(r1.dot(r2));
+    let r2 = r2.scale(1.0 / r2.norm());
+    let r3 = r1.cross(r2);
+
+    let m = [[r1.x, r2.x, r3.x], [r1.y, r2.y, r3.y], [r1.z, r2.z, r3.z]];
+
+    Transform::new(h3.scale(lambda), Rotation::from_matrix(m))
+}
+
+fn mul(a: &[[f64; 3]; 3], b: &[[f64; 3]; 3]) -> [[f64; 3]; 3] {
+    let mut out = [[0.0; 3]; 3];
+    for (i, row) in out.iter_mut().enumerate() {
+        for (j, v) in row.iter_mut().enumerate() {
+            *v = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recovers_intrinsics_from_projected_board() {
+        let (fx, fy, cx, cy) = (600.0, 610.0, 330.0, 235.0);
+        let dist = [-0.12, 0.0, 0.0, 0.0, 0.0];
+
+        let board = Board { kind: BoardKind::Chessboard, cols: 9, rows: 6, square: 0.025 };
+        let center = Translation::new(-0.1, -0.0625, 0.0);
+
+        let views: Vec<Vec<(f64, f64)>> =
+            [(0.0, 0.0, 0.0), (0.4, 0.0, 0.1), (-0.4, 0.1, -0.1), (0.0, 0.4, 0.2), (0.1, -0.4, 0.0), (0.3, 0.3, -0.2)]
+                .iter()
+                .map(|&(roll, pitch, yaw)| {
+                    let pose = Transform::new(Translation::new(0.0, 0.0, 0.5), Rotation::from_rpy(roll, pitch, yaw));
+
+                    board.points()
+                        .into_iter()
+                        .map(|p| {
+                            let c = pose.apply(p + center);
+                            distort(fx, fy, cx, cy, dist, (c.x / c.z, c.y / c.z))
+                        })
+                        .collect()
+                })
+                .collect();
+
+        let calib = calibrate(0, (640, 480), &board, &views).unwrap();
+
+        assert!((calib.fx - fx).abs() < 0.5, "fx {}", calib.fx);
+        assert!((calib.fy - fy).abs() < 0.5, "fy {}", calib.fy);
+        assert!((calib.cx - cx).abs() < 0.5, "cx {}", calib.cx);
+        assert!((calib.cy - cy).abs() < 0.5, "cy {}", calib.cy);
+        assert!((calib.dist[0] - dist[0]).abs() < 1e-3, "k1 {}", calib.dist[0]);
+        assert!(calib.error < 1e-3, "error {}", calib.error);
+    }
+}
@@ -1,5 +1,10 @@
+use crate::data::CameraTag;
+use crate::field::TAG_SIZE;
+use crate::geom::{Rotation, Transform, Translation};
+
 use dauntless::Config as DetectorConfig;
 
+use std::collections::HashMap;
 use std::env;
 use std::io::{BufReader, BufWriter};
 use std::fs::File;
@@ -13,10 +18,24 @@ use nokhwa::Camera;
 use nokhwa::pixel_format::LumaFormat;
 use nokhwa::utils::{CameraIndex, RequestedFormat, RequestedFormatType};
 
-#[derive(Clone, Copy, Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Config {
     pub detector: DetectorConfig,
     pub server: ServerConfig,
+    #[serde(default)]
+    pub pipelines: Vec<DetectorConfig>,
+    #[serde(default)]
+    pub pipeline: usize,
+    #[serde(default)]
+    pub mount: Mount,
+    #[serde(default)]
+    pub filter: Filter,
+    #[serde(default)]
+    pub std_devs: StdDevModel,
+    #[serde(default)]
+    pub track: TrackConfig,
+    #[serde(default)]
+    pub tag_sizes: HashMap<u32, f64>,
 }
 
 impl Config {
@@ -24,30 +43,306 @@ impl Config {
         Self {
             detector: DetectorConfig::default(),
             server: ServerConfig::default(index),
+            pipelines: Vec::new(),
+            pipeline: 0,
+            mount: Mount::default(),
+            filter: Filter::default(),
+            std_devs: StdDevModel::default(),
+            track: TrackConfig::default(),
+            tag_sizes: HashMap::new(),
+        }
+    }
+
+    pub fn select(&mut self, pipeline: usize) -> bool {
+        match self.pipelines.get(pipeline) {
+            Some(detector) => {
+                self.detector = *detector;
+                self.pipeline = pipeline;
+                true
+            }
+            None => false,
         }
     }
 
+    pub fn tag_size(&self, id: Option<u32>) -> f64 {
+        id.and_then(|id| self.tag_sizes.get(&id).copied()).unwrap_or(TAG_SIZE)
+    }
+
     pub fn load_all() -> Result<Vec<Self>> {
-        let file = File::open(path())?;
+        let file = File::open(path("dauntless.json"))?;
         let configs: Vec<Config> = serde_json::from_reader(BufReader::new(file))?;
         Ok(configs)
     }
 
     pub fn save_all(configs: Vec<Self>) {
-        let file = File::create(path()).unwrap();
+        let file = File::create(path("dauntless.json")).unwrap();
         let writer = BufWriter::new(file);
         serde_json::to_writer_pretty(writer, &configs).unwrap();
     }
 }
 
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Mount {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub roll: f64,
+    pub pitch: f64,
+    pub yaw: f64,
+}
+
+impl Mount {
+    pub fn transform(&self) -> Transform {
+        Transform::new(
+            Translation::new(self.x, self.y, self.z),
+            Rotation::from_rpy(self.roll.to_radians(), self.pitch.to_radians(), self.yaw.to_radians()),
+        )
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Filter {
+    pub max_ambiguity: f64,
+    pub allow: Vec<u32>,
+    pub deny: Vec<u32>,
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Self { max_ambiguity: 1.0, allow: Vec::new(), deny: Vec::new() }
+    }
+}
+
+impl Filter {
+    pub fn accepts(&self, tag: &CameraTag) -> bool {
+        let id_ok = tag.tag.id.is_none_or(|id| {
+            (self.allow.is_empty() || self.allow.contains(&id)) && !self.deny.contains(&id)
+        });
+
+        id_ok && tag.poses.ambiguity <= self.max_ambiguity
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StdDevModel {
+    pub xy: f64,
+    pub theta: f64,
+    pub single_theta: f64,
+    pub distance: f64,
+    pub ambiguity: f64,
+    pub error: f64,
+    pub max_distance: f64,
+    pub reject: f64,
+}
+
+impl Default for StdDevModel {
+    fn default() -> Self {
+        Self {
+            xy: 0.1,
+            theta: 0.2,
+            single_theta: 9999.0,
+            distance: 0.25,
+            ambiguity: 5.0,
+            error: 0.1,
+            max_distance: 6.0,
+            reject: 9999.0,
+        }
+    }
+}
+
+impl StdDevModel {
+    /// Returns x, y and theta standard deviations for a pose measured from
+    /// `tags`, using `error` as the reprojection error when a joint solve was run.
+    pub fn measure(&self, tags: &[CameraTag], error: Option<f64>) -> [f64; 3] {
+        if tags.is_empty() {
+            return [f64::MAX; 3];
+        }
+
+        let n = tags.len() as f64;
+
+        let distance = tags.iter().map(|t| t.poses.best.translation.norm()).sum::<f64>() / n;
+        let error = error.unwrap_or_else(|| tags.iter().map(|t| t.poses.best_error).sum::<f64>() / n);
+        let ambiguity = if tags.len() == 1 { tags[0].poses.ambiguity } else { 0.0 };
+
+        if distance > self.max_distance {
+            return [self.reject; 3];
+        }
+
+        let scale =
+            (1.0 + self.distance * distance * distance)
+                * (1.0 + self.ambiguity * ambiguity)
+                * (1.0 + self.error * error)
+                / n.sqrt();
+
+        let theta = if tags.len() == 1 { self.single_theta } else { self.theta * scale };
+
+        [self.xy * scale, self.xy * scale, theta]
+    }
+}
+
 #[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrackConfig {
+    pub enabled: bool,
+    pub min_hits: u32,
+    pub max_misses: u32,
+    pub gate: f64,
+    pub process_noise: f64,
+    pub measurement_noise: f64,
+    pub rotation_alpha: f64,
+}
+
+impl Default for TrackConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_hits: 2,
+            max_misses: 3,
+            gate: 0.5,
+            process_noise: 4.0,
+            measurement_noise: 0.0025,
+            rotation_alpha: 0.5,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct ServerConfig {
+    #[serde(default)]
+    pub name: String,
     pub camera: u32,
     pub res: (u32, u32),
     pub scale: u32,
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub driver_mode: bool,
+    #[serde(default)]
+    pub undistort: Undistort,
+    #[serde(default)]
+    pub latency: Option<f64>,
+    #[serde(default)]
+    pub source: Source,
+}
+
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Source {
+    #[default]
+    Camera,
+    /// Uncompressed YUV4MPEG2 (.y4m) only; only the luma plane is read, so
+    /// convert other formats first, e.g. `ffmpeg -i in.mp4 -pix_fmt gray out.y4m`.
+    Video {
+        path: String,
+        #[serde(default)]
+        fps: Option<f64>,
+        #[serde(default = "enabled")]
+        looping: bool,
+    },
+    Images {
+        path: String,
+        #[serde(default)]
+        fps: Option<f64>,
+        #[serde(default = "enabled")]
+        looping: bool,
+    },
+    Synthetic(Scene),
+    Sim(Sim),
+}
+
+/// How rendered sources draw tags: built-in tag36h11 patterns cover the
+/// bundled field layouts, images in `images` override or extend them by tag
+/// id, and `noise` and `blur` are applied after rendering.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Render {
+    pub images: Option<String>,
+    pub crop: u32,
+    pub background: f64,
+    pub noise: f64,
+    pub blur: f64,
+}
+
+impl Default for Render {
+    fn default() -> Self {
+        Self { images: None, crop: 1, background: 0.5, noise: 0.0, blur: 0.0 }
+    }
+}
+
+/// Tags rendered through a pinhole model built from the camera's `res` and
+/// `fov`, for checking detections against known poses.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Scene {
+    #[serde(flatten)]
+    pub render: Render,
+    pub tags: Vec<SceneTag>,
+    pub fps: f64,
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Self { render: Render::default(), tags: Vec::new(), fps: 30.0 }
+    }
+}
+
+/// The field layout's tags as seen from the camera's mount on a robot whose
+/// pose is read from `topic`, for running against robot code in simulation.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Sim {
+    pub topic: String,
+    #[serde(flatten)]
+    pub render: Render,
+    pub fps: f64,
+}
+
+impl Default for Sim {
+    fn default() -> Self {
+        Self { topic: "/SmartDashboard/Field/Robot".into(), render: Render::default(), fps: 30.0 }
+    }
+}
+
+/// A tag placed relative to the camera, either fixed at `pose` or moved
+/// through `path` keyframes by linear interpolation.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct SceneTag {
+    pub id: u32,
+    #[serde(default)]
+    pub size: Option<f64>,
+    #[serde(default)]
+    pub pose: Mount,
+    #[serde(default)]
+    pub path: Vec<Keyframe>,
+    #[serde(default = "enabled")]
+    pub looping: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Keyframe {
+    pub time: f64,
+    #[serde(flatten)]
+    pub pose: Mount,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Undistort {
+    #[default]
+    Off,
+    Frame,
+    Corners,
 }
 
 impl ServerConfig {
+    /// The name published to NT, falling back to the camera index when unset.
+    pub fn name(&self) -> String {
+        if self.name.is_empty() { format!("cam{}", self.camera) } else { self.name.clone() }
+    }
+
     fn default(mut index: u32) -> Self {
         let cam = loop {
             match Camera::new(
@@ -66,15 +361,218 @@ impl ServerConfig {
         };
 
         let res = cam.resolution();
+        let camera = cam.index().as_index().unwrap();
 
         Self {
+            name: format!("cam{}", camera),
             scale: 8,
-            camera: cam.index().as_index().unwrap(),
+            camera,
             res: (res.width(), res.height()),
+            enabled: true,
+            driver_mode: false,
+            undistort: Undistort::Off,
+            latency: None,
+            source: Source::Camera,
         }
     }
 }
 
-fn path() -> PathBuf {
-    env::current_exe().unwrap().parent().unwrap().join("dauntless.json")
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub nt: NtConfig,
+    pub field: Option<String>,
+    pub multitag: MultitagConfig,
+    pub filter: Filter,
+    pub record: RecordConfig,
+}
+
+/// Sizes are in megabytes.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RecordConfig {
+    pub enabled: bool,
+    pub dir: String,
+    pub segment_size: u64,
+    pub max_size: u64,
+}
+
+impl Default for RecordConfig {
+    fn default() -> Self {
+        Self { enabled: false, dir: "recordings".into(), segment_size: 64, max_size: 2048 }
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MultitagConfig {
+    pub skew: f64,
+}
+
+impl Default for MultitagConfig {
+    fn default() -> Self {
+        Self { skew: 0.02 }
+    }
+}
+
+impl Settings {
+    pub fn load() -> Self {
+        // a missing file just means defaults, but a broken one should be noticed
+        let mut settings: Settings = match File::open(path("settings.json")) {
+            Ok(file) => serde_json::from_reader(BufReader::new(file)).unwrap_or_else(|err| {
+                println!("\rmain: {} [reason: {}]", "bad settings.json, using defaults".red(), err);
+                Settings::default()
+            }),
+            Err(_) => Settings::default(),
+        };
+
+        settings.nt.apply_overrides();
+
+        if let Some(field) = opt("FIELD_LAYOUT", "--field") {
+            settings.field = Some(field);
+        }
+
+        if flag("--record") {
+            settings.record.enabled = true;
+        }
+
+        settings
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NtConfig {
+    pub team: Option<u32>,
+    pub hosts: Vec<String>,
+    pub port: u16,
+    pub name: String,
+    pub subscribe: Vec<String>,
+    pub outputs: Vec<Output>,
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Output {
+    Dauntless,
+    Photon,
+    Limelight,
+}
+
+impl Output {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "dauntless" => Some(Output::Dauntless),
+            "photon" | "photonvision" => Some(Output::Photon),
+            "limelight" => Some(Output::Limelight),
+            _ => None,
+        }
+    }
+}
+
+impl Default for NtConfig {
+    fn default() -> Self {
+        Self {
+            team: Some(4904),
+            hosts: Vec::new(),
+            port: 5810,
+            name: "dauntless".into(),
+            subscribe: vec!["/FMSInfo/".into()],
+            outputs: vec![Output::Dauntless],
+        }
+    }
+}
+
+impl NtConfig {
+    fn apply_overrides(&mut self) {
+        if let Some(team) = opt("NT_TEAM", "--team") {
+            match team.parse() {
+                Ok(team) => self.team = Some(team),
+                Err(err) => println!("\rnt: {} [reason: {}: {}]", "bad team number".red(), team, err),
+            }
+        }
+        if let Some(hosts) = opt("NT_HOST", "--host") {
+            self.hosts = list(&hosts);
+        }
+        if let Some(port) = opt("NT_PORT", "--port") {
+            match port.parse() {
+                Ok(port) => self.port = port,
+                Err(err) => println!("\rnt: {} [reason: {}: {}]", "bad port".red(), port, err),
+            }
+        }
+        if let Some(name) = opt("NT_NAME", "--name") {
+            self.name = name;
+        }
+        if let Some(subs) = opt("NT_SUBSCRIBE", "--subscribe") {
+            self.subscribe = list(&subs);
+        }
+        if let Some(outputs) = opt("NT_OUTPUT", "--output") {
+            self.outputs = list(&outputs).iter().filter_map(|o| Output::parse(o)).collect();
+        }
+    }
+
+    pub fn candidates(&self) -> Vec<String> {
+        if !self.hosts.is_empty() {
+            return self.hosts.clone();
+        }
+
+        let mut hosts = Vec::new();
+
+        if let Some(team) = self.team {
+            hosts.push(format!("10.{}.{}.2", team / 100, team % 100));
+            hosts.push(format!("roborio-{}-frc.local", team));
+        }
+
+        hosts.push("172.22.11.2".into());
+        hosts.push("localhost".into());
+
+        hosts
+    }
+
+    pub fn url(&self, host: &str) -> String {
+        format!("ws://{}:{}/nt/{}", host, self.port, self.name)
+    }
+
+    pub fn root(&self) -> String {
+        format!("/{}", self.name)
+    }
+}
+
+pub fn flag(name: &str) -> bool {
+    env::args().skip(1).any(|arg| arg == name)
+}
+
+fn arg(name: &str) -> Option<String> {
+    let mut args = env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        if arg == name {
+            return args.next();
+        }
+        if let Some(val) = arg.strip_prefix(name).and_then(|a| a.strip_prefix('=')) {
+            return Some(val.to_string());
+        }
+    }
+
+    None
+}
+
+fn list(val: &str) -> Vec<String> {
+    val
+        .split(',')
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .collect()
+}
+
+fn opt(var: &str, name: &str) -> Option<String> {
+    arg(name).or_else(|| env::var(var).ok())
+}
+
+fn enabled() -> bool {
+    true
+}
+
+pub fn path(name: &str) -> PathBuf {
+    env::current_exe().unwrap().parent().unwrap().join(name)
 }
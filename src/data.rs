@@ -1,112 +1,205 @@
+use crate::calib::{Calibration, Remap};
+use crate::config::{self, Config, Undistort};
+use crate::field::{RobotPose, TAG_SIZE};
+use crate::geom::{Intrinsics, Transform};
+use crate::record::Record;
+use crate::solve::{self, TagPoses};
+use crate::source::{self, FrameSource, Truth};
 use crate::state::State;
+use crate::track::{TrackInfo, Tracker};
 
 use dauntless::{Detector, Tag};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-use std::io::{self, Write};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
 use std::sync::Arc;
-use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
 
 use colored::Colorize;
 
-use nokhwa::Camera;
-use nokhwa::pixel_format::LumaFormat;
-use nokhwa::utils::{CameraIndex, RequestedFormat, RequestedFormatType, Resolution};
+const RETRY: Duration = Duration::from_millis(500);
 
 #[derive(Default)]
 pub struct Data {
     pub ms: Option<f32>,
+    pub fps: Option<f32>,
+    pub seq: u64,
+    pub time: Option<f64>,
+    pub capture_latency: Option<f64>,
+    pub latency: Option<f64>,
+    pub updated: Option<Instant>,
     pub tags: Vec<CameraTag>,
+    pub rejected: Vec<CameraTag>,
+    pub pose: Option<RobotPose>,
     pub frame: Option<Vec<u8>>,
     pub mask: Option<Vec<u8>>,
+    pub truth: Vec<Truth>,
 }
 
-#[derive(Clone, Copy, Serialize)]
+#[derive(Clone, Copy, Serialize, Deserialize)]
 pub struct CameraTag {
     pub camera: u32,
     pub time: f64,
+    pub robot: Transform,
+    pub poses: TagPoses,
+    pub track: Option<TrackInfo>,
     #[serde(flatten)]
     pub tag: Tag,
 }
 
 pub fn update(state: &Arc<State>) {
-    let (mut cam_idx, mut w, mut h, mut scale) = {
-        let config = state.config();
-        (config.server.camera, config.server.res.0, config.server.res.1, config.server.scale)
-    };
+    let mut scale = state.config().server.scale;
 
-    let mut camera = create_camera(cam_idx, w, h);
+    let mut key = None;
+    let mut source: Option<Box<dyn FrameSource>> = None;
     let mut scale_knl = create_kernel(scale);
     let mut detector = Detector::new();
+    let mut tracker = Tracker::default();
 
     let mut tick = 0;
+    let mut seq = 0;
+
+    let mut fps = None;
+    let mut last_frame = Instant::now();
+    let timing = Timing::new();
 
-    let mut data = vec![0.0; (w * h) as usize];
-    let mut fs = vec![0; (w * h) as usize];
-    let mut rsz = vec![0; (w / scale * h / scale) as usize];
+    let (mut w, mut h) = (0, 0);
+    let mut data = Vec::new();
+    let mut undist = Vec::new();
+    let mut remap: Option<Remap> = None;
+    let mut fs = Vec::new();
+    let mut rsz = Vec::new();
 
     loop {
-        (cam_idx, w, h, scale) = {
-            let config = state.config();
-
-            let (new_idx, new_w, new_h, new_scale) =
-                (config.server.camera, config.server.res.0, config.server.res.1, config.server.scale);
-
-            if (cam_idx, w, h) != (new_idx, new_w, new_h) {
-                camera.stop_stream().unwrap();
-
-                if cam_idx != new_idx || cfg!(target_os = "macos") {
-                    camera = create_camera(new_idx, new_w, new_h);
-                } else {
-                    camera.set_camera_requset(
-                        RequestedFormat::new::<LumaFormat>(
-                            RequestedFormatType::HighestResolution(
-                                Resolution::new(new_w, new_h),
-                            ),
-                        ),
-                    ).unwrap();
-                    camera.open_stream().unwrap();
+        let current = state.config().clone();
+        let (server, new_scale) = (&current.server, current.server.scale);
+        let new_key = (server.source.clone(), server.camera, server.res, current.detector.fov);
+
+        if key.as_ref() != Some(&new_key) {
+            let resized =
+                match (source.as_mut(), &key) {
+                    (Some(src), Some((s, c, _, f))) if (s, c, f) == (&new_key.0, &new_key.1, &new_key.3) =>
+                        src.set_resolution(new_key.2).unwrap_or(false),
+                    _ => false,
+                };
+
+            if !resized {
+                source = None;
+
+                match source::open(state, &current) {
+                    Ok(src) => source = Some(src),
+                    Err(err) => {
+                        println!("\rdata: {} [reason: {}]", "source failed".red(), err);
+                        thread::sleep(RETRY);
+                        continue;
+                    }
                 }
-
-                data = vec![0.0; (new_w * new_h) as usize];
-                fs = vec![0; (new_w * new_h) as usize];
-                rsz = vec![0; (new_w / new_scale * new_h / new_scale) as usize];
             }
-            if scale != new_scale {
-                scale_knl = create_kernel(new_scale);
-                rsz = vec![0; (new_w / new_scale * new_h / new_scale) as usize];
+
+            key = Some(new_key);
+        }
+
+        let Some(src) = source.as_mut() else { continue };
+
+        let frame = match src.frame() {
+            Ok(Some(frame)) => frame,
+            Ok(None) => {
+                thread::sleep(RETRY);
+                continue;
             }
+            Err(err) => {
+                println!("\rdata: {} [reason: {}]", "frame failed".red(), err);
+
+                source = None;
+                key = None;
+                thread::sleep(RETRY);
 
-            (new_idx, new_w, new_h, new_scale)
+                continue;
+            }
         };
+        let returned = Instant::now();
+
+        if (frame.width, frame.height) != (w, h) || scale != new_scale {
+            (w, h, scale) = (frame.width, frame.height, new_scale);
 
-        let frame = camera.frame().unwrap();
-        let frame_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs_f64();
+            scale_knl = create_kernel(scale);
+            data = vec![0.0; (w * h) as usize];
+            fs = vec![0; (w * h) as usize];
+            rsz = vec![0; (w / scale * h / scale) as usize];
 
-        let decoded = frame.decode_image::<LumaFormat>().unwrap();
-        let bytes = decoded.to_vec();
+            // sources that can't be asked for a resolution report their own
+            if server.res != (w, h) {
+                state.config().server.res = (w, h);
+                key = key.map(|(s, c, _, f)| (s, c, (w, h), f));
+            }
+        }
+
+        let interval = returned.duration_since(last_frame).as_secs_f32();
+        last_frame = returned;
+
+        if interval > 0.0 {
+            let cur = 1.0 / interval;
+            fps = Some(fps.map_or(cur, |f: f32| f * 0.9 + cur * 0.1));
+        }
+        seq += 1;
+
+        let (frame_time, capture_latency) = timing.stamp(returned, frame.captured, server.latency, fps);
 
         let start = Instant::now();
 
-        let stride = decoded.len() as u32 / h;
-        for y in 0..h {
-            let src = (y * stride) as usize;
-            let dst = (y * w) as usize;
+        for (d, &v) in data.iter_mut().zip(&frame.luma) {
+            *d = v as f32 / 255.0;
+        }
 
-            for x in 0..w as usize {
-                data[dst + x] = bytes[src + x] as f32 / 255.0;
+        if state.snapshot.swap(false, Ordering::Relaxed) {
+            match snapshot(&state.name(), w, h, &data) {
+                Ok(path) => println!("\rdata: {} [path: {}]", "saved snapshot".green(), path.display()),
+                Err(err) => println!("\rdata: {} [reason: {}]", "snapshot failed".red(), err),
             }
         }
 
-        let (tags, mask) = detector.process(
-            w as usize,
-            h as usize,
-            &state.config().detector,
-            &data,
-        );
+        if state.capture.swap(false, Ordering::Relaxed) {
+            if let Some(session) = state.calib().as_mut() {
+                session.capture(w, h, &data);
+            }
+        }
+
+        let config = state.config().clone();
+        let active = config.server.enabled && !config.server.driver_mode;
+
+        let calib = state.calibration().filter(|c| c.res == (w, h));
+
+        if let (Undistort::Frame, Some(calib)) = (config.server.undistort, &calib) {
+            let table = match remap.take() {
+                Some(r) if r.calib == *calib => r,
+                _ => Remap::new(calib.clone()),
+            };
+
+            undist.resize(data.len(), 0.0);
+            table.apply(&data, &mut undist);
+            std::mem::swap(&mut data, &mut undist);
+
+            remap = Some(table);
+        }
+
+        let detect_start = Instant::now();
+        let processed =
+            active.then(|| detector.process(
+                w as usize,
+                h as usize,
+                &config.detector,
+                &data,
+            ));
 
         let now = Instant::now();
         let ms = now.duration_since(start).as_secs_f32() * 1000.0;
+        let detect_ms = now.duration_since(detect_start).as_secs_f32() * 1000.0;
 
         if tick % 10 == 0 {
             print!(
@@ -122,21 +215,70 @@ pub fn update(state: &Arc<State>) {
 
         encode(w, h, scale, &scale_knl, &data, &mut fs, &mut rsz);
         let fm = rsz.clone();
-        encode(w, h, scale, &scale_knl, &mask, &mut fs, &mut rsz);
-        let mm = rsz.clone();
+
+        let (tags, mm) = match processed {
+            Some((tags, mask)) => {
+                encode(w, h, scale, &scale_knl, &mask, &mut fs, &mut rsz);
+                (tags, rsz.clone())
+            }
+            None => (Vec::new(), vec![0; rsz.len()]),
+        };
+
+        let corner_calib = calib.filter(|_| config.server.undistort == Undistort::Corners);
+        let (cam_tags, rejected) = camera_tags(state, &config, corner_calib.as_ref(), frame_time, &tags);
+        let mount = config.mount.transform();
 
         let cam_tags =
-            tags
-                .iter()
-                .map(|t| CameraTag { time: frame_time, camera: state.id, tag: *t })
-                .collect();
+            if config.track.enabled {
+                tracker.update(&config.track, mount, frame_time, &cam_tags)
+            } else {
+                tracker = Tracker::default();
+                cam_tags
+            };
+
+        let pose = state.field().estimate(&cam_tags).map(|mut pose| {
+            let used: Vec<CameraTag> =
+                cam_tags
+                    .iter()
+                    .filter(|t| t.tag.id.is_some_and(|id| pose.ids.contains(&id)))
+                    .copied()
+                    .collect();
+
+            pose.std_devs = config.std_devs.measure(&used, None);
+            pose
+        });
+
+        if state.recorder.active() {
+            let record = Record {
+                camera: state.id,
+                seq,
+                time: frame_time,
+                width: w,
+                height: h,
+                ms: detect_ms,
+                frame: Vec::new(),
+                tags: cam_tags.clone(),
+                rejected: rejected.clone(),
+            };
+
+            state.recorder.record(&config, &state.filter, state.calibration(), frame.luma, record);
+        }
 
         {
             let update = Data {
                 tags: cam_tags,
+                rejected,
+                pose,
                 ms: Some(ms),
+                fps,
+                seq,
+                time: Some(frame_time),
+                capture_latency: Some(capture_latency),
+                latency: Some((timing.unix(Instant::now()) - frame_time) * 1000.0),
+                updated: Some(now),
                 frame: Some(fm),
                 mask: Some(mm),
+                truth: frame.truth,
             };
 
             *state.data() = update;
@@ -147,19 +289,111 @@ pub fn update(state: &Arc<State>) {
     }
 }
 
-fn create_camera(index: u32, width: u32, height: u32) -> Camera {
-    let index = CameraIndex::Index(index);
+/// Converts monotonic instants to unix seconds against a fixed anchor, so
+/// frame times never jump with wall clock adjustments.
+struct Timing {
+    instant: Instant,
+    unix: f64,
+}
+
+impl Timing {
+    fn new() -> Self {
+        Self {
+            instant: Instant::now(),
+            unix: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs_f64(),
+        }
+    }
+
+    fn unix(&self, at: Instant) -> f64 {
+        self.unix + at.duration_since(self.instant).as_secs_f64()
+    }
+
+    /// Returns the capture time and the capture latency in milliseconds.
+    /// Without a source timestamp the frame is assumed to have waited one
+    /// frame interval in the driver, unless a fixed latency is configured.
+    fn stamp(&self, returned: Instant, captured: Option<Instant>, manual: Option<f64>, fps: Option<f32>) -> (f64, f64) {
+        let ret = self.unix(returned);
+
+        if let Some(captured) = captured {
+            return (self.unix(captured), returned.duration_since(captured).as_secs_f64() * 1000.0);
+        }
+
+        let latency = manual.unwrap_or_else(|| fps.map_or(0.0, |f| 1000.0 / f as f64));
+        (ret - latency / 1000.0, latency)
+    }
+}
+
+/// Solves the frame's detections and splits them into accepted and
+/// rejected tags.
+fn camera_tags(
+    state: &State,
+    config: &Config,
+    calib: Option<&Calibration>,
+    time: f64,
+    tags: &[Tag],
+) -> (Vec<CameraTag>, Vec<CameraTag>) {
+    solve_tags(state.id, config, state.intrinsics(), calib, time, tags)
+        .into_iter()
+        .partition(|t| config.filter.accepts(t) && state.filter.accepts(t))
+}
+
+/// Scales detections to their configured physical size, undistorts their
+/// corners if a calibration is given and solves their poses.
+pub fn solve_tags(
+    camera: u32,
+    config: &Config,
+    intr: Intrinsics,
+    calib: Option<&Calibration>,
+    time: f64,
+    tags: &[Tag],
+) -> Vec<CameraTag> {
+    let mount = config.mount.transform();
+
+    // an unsolved pose has no error to filter or weight by, so drop the tag
+    tags
+        .iter()
+        .filter_map(|t| {
+            let size = config.tag_size(t.id);
+
+            let mut tag = *t;
+            tag.pos = tag.pos.map(|v| v * (size / TAG_SIZE) as f32);
+
+            if let Some(calib) = calib {
+                tag.corners = tag.corners.map(|(x, y)| {
+                    let (u, v) = calib.undistort((x as f64, y as f64));
+                    (u as f32, v as f32)
+                });
+            }
+
+            let initial = Transform::from_tag(&tag);
+            let corners = tag.corners.map(|(x, y)| (x as f64, y as f64));
+
+            let poses = solve::tag_poses(intr, size, corners, initial)?;
+
+            Some(CameraTag {
+                time,
+                camera,
+                robot: mount * poses.best,
+                poses,
+                track: None,
+                tag,
+            })
+        })
+        .collect()
+}
+
+fn snapshot(name: &str, w: u32, h: u32, data: &[f32]) -> Result<PathBuf> {
+    let dir = config::path("snapshots");
+    fs::create_dir_all(&dir)?;
 
-    let requested = RequestedFormat::new::<LumaFormat>(
-        RequestedFormatType::HighestResolution(
-            Resolution::new(width, height),
-        ),
-    );
+    let millis = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+    let path = dir.join(format!("{}-{}.pgm", name, millis));
 
-    let mut camera = Camera::new(index, requested).unwrap();
-    camera.open_stream().unwrap();
+    let mut file = BufWriter::new(File::create(&path)?);
+    write!(file, "P5\n{} {}\n255\n", w, h)?;
+    file.write_all(&data.iter().map(|v| (v * 255.0) as u8).collect::<Vec<u8>>())?;
 
-    camera
+    Ok(path)
 }
 
 fn create_kernel(scale: u32) -> Vec<f32> {
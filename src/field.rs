@@ -0,0 +1,124 @@
+use crate::config;
+use crate::data::CameraTag;
+use crate::geom::{Rotation, Transform, Translation};
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+pub const LAYOUTS: &[(&str, &str)] = &[
+    ("2025-reefscape", include_str!("../layouts/2025-reefscape.json")),
+];
+
+pub const CUSTOM: &str = "custom";
+
+pub const TAG_SIZE: f64 = 0.1651;
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Layout {
+    pub tags: Vec<LayoutTag>,
+    pub field: FieldSize,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LayoutTag {
+    #[serde(rename = "ID")]
+    pub id: u32,
+    pub pose: LayoutPose,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct LayoutPose {
+    pub translation: Translation,
+    pub rotation: LayoutRotation,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct LayoutRotation {
+    pub quaternion: Quaternion,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub struct Quaternion {
+    pub w: f64,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+pub struct FieldSize {
+    pub length: f64,
+    pub width: f64,
+}
+
+#[derive(Clone, Serialize)]
+pub struct RobotPose {
+    pub pose: Transform,
+    pub time: f64,
+    pub ids: Vec<u32>,
+    pub std_devs: [f64; 3],
+}
+
+impl Layout {
+    pub fn load(name: Option<&str>) -> Result<Self> {
+        match name {
+            Some(CUSTOM) => Self::custom(),
+            Some(name) => Self::bundled(name),
+            None => Self::custom().or_else(|_| Self::bundled(LAYOUTS[0].0)),
+        }
+    }
+
+    pub fn bundled(name: &str) -> Result<Self> {
+        let (_, json) =
+            LAYOUTS
+                .iter()
+                .find(|(n, _)| *n == name)
+                .ok_or_else(|| anyhow!("unknown field layout: {}", name))?;
+
+        Ok(serde_json::from_str(json)?)
+    }
+
+    pub fn custom() -> Result<Self> {
+        let file = File::open(config::path("field.json"))?;
+        Ok(serde_json::from_reader(BufReader::new(file))?)
+    }
+
+    pub fn save_custom(&self) -> Result<()> {
+        let file = File::create(config::path("field.json"))?;
+        serde_json::to_writer_pretty(BufWriter::new(file), self)?;
+        Ok(())
+    }
+
+    pub fn pose(&self, id: u32) -> Option<Transform> {
+        self.tags
+            .iter()
+            .find(|t| t.id == id)
+            .map(|t| {
+                let q = t.pose.rotation.quaternion;
+                Transform::new(
+                    t.pose.translation,
+                    Rotation { w: q.w, x: q.x, y: q.y, z: q.z }.normalize(),
+                )
+            })
+    }
+
+    pub fn estimate(&self, tags: &[CameraTag]) -> Option<RobotPose> {
+        let (poses, ids): (Vec<Transform>, Vec<u32>) =
+            tags
+                .iter()
+                .filter_map(|t| {
+                    let id = t.tag.id?;
+                    let field_to_tag = self.pose(id)?;
+                    Some((field_to_tag * t.robot.inverse(), id))
+                })
+                .unzip();
+
+        let time = tags.first()?.time;
+
+        Transform::average(&poses).map(|pose| RobotPose { pose, time, ids, std_devs: [f64::MAX; 3] })
+    }
+}
@@ -0,0 +1,257 @@
+use std::f64::consts::PI;
+use std::ops::{Add, Mul, Neg, Sub};
+
+use dauntless::Tag;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Translation {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Translation {
+    pub fn new(x: f64, y: f64, z: f64) -> Self {
+        Self { x, y, z }
+    }
+
+    pub fn cross(self, o: Self) -> Self {
+        Self::new(
+            self.y * o.z - self.z * o.y,
+            self.z * o.x - self.x * o.z,
+            self.x * o.y - self.y * o.x,
+        )
+    }
+
+    pub fn scale(self, s: f64) -> Self {
+        Self::new(self.x * s, self.y * s, self.z * s)
+    }
+
+    pub fn dot(self, o: Self) -> f64 {
+        self.x * o.x + self.y * o.y + self.z * o.z
+    }
+
+    pub fn norm(self) -> f64 {
+        self.dot(self).sqrt()
+    }
+}
+
+impl Add for Translation {
+    type Output = Self;
+
+    fn add(self, o: Self) -> Self {
+        Self::new(self.x + o.x, self.y + o.y, self.z + o.z)
+    }
+}
+
+impl Sub for Translation {
+    type Output = Self;
+
+    fn sub(self, o: Self) -> Self {
+        Self::new(self.x - o.x, self.y - o.y, self.z - o.z)
+    }
+}
+
+impl Neg for Translation {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::new(-self.x, -self.y, -self.z)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Rotation {
+    pub w: f64,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Default for Rotation {
+    fn default() -> Self {
+        Self { w: 1.0, x: 0.0, y: 0.0, z: 0.0 }
+    }
+}
+
+impl Rotation {
+    pub fn from_rpy(roll: f64, pitch: f64, yaw: f64) -> Self {
+        let (sr, cr) = (roll / 2.0).sin_cos();
+        let (sp, cp) = (pitch / 2.0).sin_cos();
+        let (sy, cy) = (yaw / 2.0).sin_cos();
+
+        Self {
+            w: cr * cp * cy + sr * sp * sy,
+            x: sr * cp * cy - cr * sp * sy,
+            y: cr * sp * cy + sr * cp * sy,
+            z: cr * cp * sy - sr * sp * cy,
+        }
+    }
+
+    pub fn from_yaw(yaw: f64) -> Self {
+        Self::from_rpy(0.0, 0.0, yaw)
+    }
+
+    pub fn from_rotvec(v: Translation) -> Self {
+        let angle = v.norm();
+        if angle < 1e-12 {
+            return Self { w: 1.0, x: v.x / 2.0, y: v.y / 2.0, z: v.z / 2.0 }.normalize();
+        }
+
+        let (s, c) = (angle / 2.0).sin_cos();
+        let axis = v.scale(1.0 / angle);
+
+        Self { w: c, x: axis.x * s, y: axis.y * s, z: axis.z * s }
+    }
+
+    pub fn from_matrix(m: [[f64; 3]; 3]) -> Self {
+        let trace = m[0][0] + m[1][1] + m[2][2];
+
+        let q =
+            if trace > 0.0 {
+                let s = (trace + 1.0).sqrt() * 2.0;
+                Self { w: s / 4.0, x: (m[2][1] - m[1][2]) / s, y: (m[0][2] - m[2][0]) / s, z: (m[1][0] - m[0][1]) / s }
+            } else if m[0][0] > m[1][1] && m[0][0] > m[2][2] {
+                let s = (1.0 + m[0][0] - m[1][1] - m[2][2]).sqrt() * 2.0;
+                Self { w: (m[2][1] - m[1][2]) / s, x: s / 4.0, y: (m[0][1] + m[1][0]) / s, z: (m[0][2] + m[2][0]) / s }
+            } else if m[1][1] > m[2][2] {
+                let s = (1.0 + m[1][1] - m[0][0] - m[2][2]).sqrt() * 2.0;
+                Self { w: (m[0][2] - m[2][0]) / s, x: (m[0][1] + m[1][0]) / s, y: s / 4.0, z: (m[1][2] + m[2][1]) / s }
+            } else {
+                let s = (1.0 + m[2][2] - m[0][0] - m[1][1]).sqrt() * 2.0;
+                Self { w: (m[1][0] - m[0][1]) / s, x: (m[0][2] + m[2][0]) / s, y: (m[1][2] + m[2][1]) / s, z: s / 4.0 }
+            };
+
+        q.normalize()
+    }
+
+    pub fn rpy(self) -> (f64, f64, f64) {
+        let Self { w, x, y, z } = self;
+
+        let roll = (2.0 * (w * x + y * z)).atan2(1.0 - 2.0 * (x * x + y * y));
+        let pitch = (2.0 * (w * y - z * x)).clamp(-1.0, 1.0).asin();
+        let yaw = (2.0 * (w * z + x * y)).atan2(1.0 - 2.0 * (y * y + z * z));
+
+        (roll, pitch, yaw)
+    }
+
+    pub fn inverse(self) -> Self {
+        Self { w: self.w, x: -self.x, y: -self.y, z: -self.z }
+    }
+
+    pub fn normalize(self) -> Self {
+        let n = (self.w * self.w + self.x * self.x + self.y * self.y + self.z * self.z).sqrt();
+        Self { w: self.w / n, x: self.x / n, y: self.y / n, z: self.z / n }
+    }
+
+    pub fn rotate(self, v: Translation) -> Translation {
+        let q = Translation::new(self.x, self.y, self.z);
+        let t = q.cross(v).scale(2.0);
+
+        v + t.scale(self.w) + q.cross(t)
+    }
+}
+
+impl Mul for Rotation {
+    type Output = Self;
+
+    fn mul(self, o: Self) -> Self {
+        Self {
+            w: self.w * o.w - self.x * o.x - self.y * o.y - self.z * o.z,
+            x: self.w * o.x + self.x * o.w + self.y * o.z - self.z * o.y,
+            y: self.w * o.y - self.x * o.z + self.y * o.w + self.z * o.x,
+            z: self.w * o.z + self.x * o.y - self.y * o.x + self.z * o.w,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Transform {
+    pub translation: Translation,
+    pub rotation: Rotation,
+}
+
+impl Transform {
+    pub fn new(translation: Translation, rotation: Rotation) -> Self {
+        Self { translation, rotation }
+    }
+
+    pub fn from_tag(tag: &Tag) -> Self {
+        let [x, y, z] = tag.pos;
+
+        Self::new(
+            Translation::new(z as f64, -x as f64, -y as f64),
+            Rotation::from_yaw(PI + tag.rot as f64),
+        )
+    }
+
+    pub fn inverse(self) -> Self {
+        let rotation = self.rotation.inverse();
+        Self::new(rotation.rotate(-self.translation), rotation)
+    }
+
+    pub fn average(transforms: &[Self]) -> Option<Self> {
+        let first = transforms.first()?;
+        let n = transforms.len() as f64;
+
+        let mut translation = Translation::default();
+        let mut sum = Rotation { w: 0.0, x: 0.0, y: 0.0, z: 0.0 };
+
+        for t in transforms {
+            translation = translation + t.translation.scale(1.0 / n);
+
+            let r = t.rotation;
+            let dot = r.w * first.rotation.w + r.x * first.rotation.x + r.y * first.rotation.y + r.z * first.rotation.z;
+            let s = if dot < 0.0 { -1.0 } else { 1.0 };
+
+            sum = Rotation { w: sum.w + r.w * s, x: sum.x + r.x * s, y: sum.y + r.y * s, z: sum.z + r.z * s };
+        }
+
+        Some(Self::new(translation, sum.normalize()))
+    }
+
+    pub fn apply(self, v: Translation) -> Translation {
+        self.rotation.rotate(v) + self.translation
+    }
+}
+
+impl Mul for Transform {
+    type Output = Self;
+
+    fn mul(self, o: Self) -> Self {
+        Self::new(self.apply(o.translation), (self.rotation * o.rotation).normalize())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Intrinsics {
+    pub fx: f64,
+    pub fy: f64,
+    pub cx: f64,
+    pub cy: f64,
+}
+
+impl Intrinsics {
+    pub fn from_fov(w: u32, h: u32, fov: f64) -> Self {
+        let f = w as f64 / 2.0 / (fov.to_radians() / 2.0).tan();
+
+        Self {
+            fx: f,
+            fy: f,
+            cx: w as f64 / 2.0,
+            cy: h as f64 / 2.0,
+        }
+    }
+
+    pub fn project(&self, p: Translation) -> Option<(f64, f64)> {
+        (p.x > 1e-6).then(|| (self.cx - self.fx * p.y / p.x, self.cy - self.fy * p.z / p.x))
+    }
+
+    pub fn angles(&self, (u, v): (f64, f64)) -> (f64, f64) {
+        let yaw = ((u - self.cx) / self.fx).atan();
+        let pitch = ((self.cy - v) / self.fy).atan();
+
+        (yaw, pitch)
+    }
+}
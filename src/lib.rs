@@ -0,0 +1,16 @@
+#[macro_use] extern crate rocket;
+
+pub mod calib;
+pub mod config;
+pub mod data;
+pub mod field;
+pub mod geom;
+pub mod meta;
+pub mod multitag;
+pub mod nt;
+pub mod record;
+pub mod solve;
+pub mod source;
+pub mod state;
+pub mod track;
+pub mod web;
@@ -1,13 +1,10 @@
 #[macro_use] extern crate rocket;
 
-mod config;
-mod data;
-mod meta;
-mod nt;
-mod state;
-mod web;
-
-use crate::state::States;
+use dauntless_srv::{config, nt, web};
+use dauntless_srv::config::Settings;
+use dauntless_srv::field::Layout;
+use dauntless_srv::nt::mock::MockServer;
+use dauntless_srv::state::States;
 
 use colored::Colorize;
 
@@ -21,12 +18,38 @@ async fn rocket() -> _ {
 
     println!("main: {} [{} camera{}]", "running".green(), n_cams, if n_cams != 1 { "s" } else { "" });
 
-    let states = States::new(n_cams);
+    let mut settings = Settings::load();
+
+    let mock = if config::flag("--mock-nt") {
+        let server = match MockServer::bind(("127.0.0.1", settings.nt.port)).await {
+            Ok(server) => server,
+            Err(err) => {
+                println!("\rmock: {} [reason: {}]", "bind failed".red(), err);
+                std::process::exit(1);
+            }
+        };
+        println!("mock: {} [addr: {}]", "listening".green(), server.addr());
+
+        settings.nt.hosts = vec!["localhost".into()];
+        Some(server)
+    } else {
+        None
+    };
+
+    let layout = Layout::load(settings.field.as_deref()).unwrap_or_else(|err| {
+        println!("main: {} [reason: {}]", "no field layout".red(), err);
+        Layout::default()
+    });
+
+    let states = States::new(n_cams, layout, settings.filter.clone(), settings.record.clone());
 
     let sts = states.states.clone();
+    let tpcs = states.topics.clone();
+    let clk = states.clock.clone();
     let ntfy = states.notify.clone();
+    let rec = states.recorder.clone();
 
-    tokio::spawn(nt::run(sts, ntfy));
+    tokio::spawn(nt::run(settings, sts, tpcs, clk, ntfy, rec));
 
-    web::build(states)
+    web::build(states, mock)
 }
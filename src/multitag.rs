@@ -0,0 +1,132 @@
+use crate::config::{Config, StdDevModel};
+use crate::data::CameraTag;
+use crate::field::Layout;
+use crate::geom::{Intrinsics, Transform};
+use crate::solve::{self, Observation, Solution};
+use crate::state::State;
+
+use std::sync::Arc;
+
+use serde::Serialize;
+
+#[derive(Clone, Serialize)]
+pub struct Fused {
+    #[serde(flatten)]
+    pub solution: Solution,
+    pub time: f64,
+    pub cameras: Vec<u32>,
+    pub std_devs: [f64; 3],
+}
+
+struct Frame {
+    time: f64,
+    model: StdDevModel,
+    tags: Vec<CameraTag>,
+    obs: Vec<Observation>,
+}
+
+/// Jointly solves the robot pose from every camera whose latest frame lies
+/// within `skew` seconds of the newest one. Each camera's own std dev model
+/// rates the tags it saw, and the results are combined by inverse variance.
+pub fn fuse(states: &[Arc<State>], skew: f64) -> Option<Fused> {
+    let frames: Vec<(u32, Frame)> =
+        states
+            .iter()
+            .filter_map(|st| frame(st).map(|f| (st.id, f)))
+            .collect();
+
+    let newest = frames.iter().map(|(_, f)| f.time).fold(f64::MIN, f64::max);
+
+    let (cameras, frames): (Vec<u32>, Vec<Frame>) =
+        frames
+            .into_iter()
+            .filter(|(_, f)| newest - f.time <= skew)
+            .unzip();
+
+    let tags: Vec<CameraTag> = frames.iter().flat_map(|f| f.tags.clone()).collect();
+    let obs: Vec<Observation> = frames.iter().flat_map(|f| f.obs.iter().copied()).collect();
+
+    let guess = states.first()?.field().estimate(&tags)?.pose;
+    let solution = solve::solve_pose(&obs, guess)?;
+
+    let per_camera: Vec<[f64; 3]> =
+        frames
+            .iter()
+            .filter_map(|f| {
+                let used: Vec<CameraTag> =
+                    f.tags
+                        .iter()
+                        .filter(|t| t.tag.id.is_some_and(|id| solution.ids.contains(&id)))
+                        .copied()
+                        .collect();
+
+                (!used.is_empty()).then(|| f.model.measure(&used, Some(solution.error)))
+            })
+            .collect();
+    let std_devs = combine(&per_camera);
+
+    Some(Fused { solution, time: newest, cameras, std_devs })
+}
+
+fn combine(std_devs: &[[f64; 3]]) -> [f64; 3] {
+    std::array::from_fn(|i| {
+        let info: f64 = std_devs.iter().map(|s| 1.0 / (s[i] * s[i])).sum();
+        if info > 0.0 { 1.0 / info.sqrt() } else { f64::MAX }
+    })
+}
+
+/// Solves the field-to-camera pose from a single camera's tags.
+pub fn camera(st: &State) -> Option<Solution> {
+    let mount = st.config().mount.transform();
+    let Frame { tags, mut obs, .. } = frame(st)?;
+
+    if obs.len() < 2 {
+        return None;
+    }
+
+    for o in &mut obs {
+        o.robot_to_camera = Transform::default();
+    }
+
+    let guess = st.field().estimate(&tags)?.pose * mount;
+    solve::solve_pose(&obs, guess)
+}
+
+fn frame(st: &State) -> Option<Frame> {
+    let config = st.config().clone();
+    let intr = st.intrinsics();
+    let mount = config.mount.transform();
+
+    let (time, tags) = {
+        let data = st.data();
+        (data.time?, data.tags.clone())
+    };
+
+    let obs = observations(&st.field(), &config, &tags, intr, mount);
+
+    (!obs.is_empty()).then_some(Frame { time, model: config.std_devs, tags, obs })
+}
+
+fn observations(
+    layout: &Layout,
+    config: &Config,
+    tags: &[CameraTag],
+    intr: Intrinsics,
+    mount: Transform,
+) -> Vec<Observation> {
+    tags
+        .iter()
+        .filter_map(|t| {
+            let id = t.tag.id?;
+
+            Some(Observation {
+                id,
+                robot_to_camera: mount,
+                intr,
+                field_to_tag: layout.pose(id)?,
+                size: config.tag_size(Some(id)),
+                corners: t.tag.corners.map(|(x, y)| (x as f64, y as f64)),
+            })
+        })
+        .collect()
+}
@@ -1,119 +1,214 @@
+mod client;
+mod clock;
+mod control;
+mod limelight;
+mod photon;
+mod structs;
+mod targets;
+mod topics;
+
+pub mod mock;
+
+pub use self::clock::{Clock, Sample};
+pub use self::structs::unpack_pose;
+pub use self::topics::{Raw, Topic, Topics, Value};
+use self::client::NT;
+use self::control::Control;
+use self::targets::View;
+use self::structs::{Pose3d, Struct, SCHEMAS};
+
+use crate::config::{MultitagConfig, NtConfig, Output, Settings, Source};
 use crate::data::CameraTag;
+use crate::geom::Transform;
+use crate::multitag;
+use crate::record::Recorder;
 use crate::state::State;
 
+use std::collections::HashSet;
 use std::sync::Arc;
-use std::thread;
-use std::net::TcpStream;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use colored::Colorize;
-use serde::Serialize;
 
 use tokio::sync::Notify;
-use tungstenite::{Message, WebSocket};
-use tungstenite::stream::MaybeTlsStream;
-use tungstenite::client::IntoClientRequest;
+use tokio::time::{self, MissedTickBehavior};
 
-const HOST: &str = "ws://10.49.4.2:5810/nt/dauntless";
+const BACKOFF_MIN: Duration = Duration::from_millis(250);
+const BACKOFF_MAX: Duration = Duration::from_millis(8000);
 
-const UID_JSON: u32 = 16;
-const UID_IDS: u32 = 8;
-const UID_TIME: u32 = 4;
+const STALE_TIMEOUT: Duration = Duration::from_millis(1000);
+const TICK_INTERVAL: Duration = Duration::from_millis(100);
 
-const TYPE_JSON: u32 = 4;
-const TYPE_INTLIST: u32 = 18;
-const TYPE_DOUBLE: u32 = 1;
-const TYPE_INT: u32 = 2;
+pub async fn run(
+    settings: Settings,
+    states: Vec<Arc<State>>,
+    topics: Arc<Topics>,
+    clock: Arc<Clock>,
+    notify: Arc<Notify>,
+    recorder: Arc<Recorder>,
+) {
+    let config = settings.nt;
 
-struct NT {
-    ws: WebSocket<MaybeTlsStream<TcpStream>>,
-    delta: i64,
-}
+    let mut backoff = BACKOFF_MIN;
+    let mut control = Control::default();
+
+    loop {
+        topics.clear();
+
+        let (mut nt, host) = match init(&config, &clock, &topics).await {
+            Ok(nt) => nt,
+            Err(err) => {
+                println!("\rnt: {} [reason: {}]", "init failed".red(), err);
+
+                time::sleep(backoff).await;
+                backoff = (backoff * 2).min(BACKOFF_MAX);
+
+                continue;
+            }
+        };
 
-impl NT {
-    fn new() -> Result<Self> {
-        let mut req = HOST.into_client_request()?;
-        req.headers_mut().insert(
-            "Sec-WebSocket-Protocol",
-            "v4.1.networktables.first.wpi.edu".parse()?,
-        );
+        backoff = BACKOFF_MIN;
+        println!("\rnt: {} [host: {}]", "connected".green(), host);
 
-        let (mut ws, _) = tungstenite::connect(req)?;
+        let mut interval = time::interval(TICK_INTERVAL);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
 
-        let local = now();
-        let buf = rmp_serde::to_vec(&(-1i64, 0i64, TYPE_INT, local))?;
+        let mut subscribed = HashSet::new();
 
-        ws.send(Message::Binary(buf.into()))?;
-        let msg = ws.read()?;
+        loop {
+            if let Err(err) = subscribe_new(&mut nt, &config.root(), &states, &mut subscribed).await {
+                println!("\rnt: {} [reason: {}]", "subscribe failed".red(), err);
+                break;
+            }
 
-        let (_, server, _, t0): (i64, i64, u32, i64) = rmp_serde::from_slice(&msg.into_data())?;
-        let t1 = now();
+            let res = tick(&mut nt, &config.outputs, &settings.multitag, &states, &topics, &recorder, &mut control).await;
 
-        let tt = (t1 - t0) / 2;
-        let delta = server - (t1 - tt);
+            if let Err(err) = res {
+                println!("\rnt: {} [reason: {}]", "tick failed".red(), err);
+                break;
+            }
 
-        Ok(Self { ws, delta })
+            tokio::select! {
+                res = nt.closed() => {
+                    let reason = res.err().map(|e| e.to_string()).unwrap_or_default();
+                    println!("\rnt: {} [reason: {}]", "disconnected".red(), reason);
+                    break;
+                }
+                _ = notify.notified() => {}
+                // keeps health and control topics live when no frames arrive
+                _ = interval.tick() => {}
+            }
+        }
     }
+}
+
+async fn init(config: &NtConfig, clock: &Arc<Clock>, topics: &Arc<Topics>) -> Result<(NT, String)> {
+    let mut last = anyhow!("no hosts configured");
 
-    fn publish(&mut self, topic: &str, uid: u32, ty: &str) -> Result<()> {
-        let msg = serde_json::json!([{
-            "method": "publish",
-            "params": {
-                "name": topic,
-                "pubuid": uid,
-                "type": ty,
-                "properties": {},
-            },
-        }]);
-        self.ws.send(Message::Text(msg.to_string().into()))?;
-
-        Ok(())
+    for host in config.candidates() {
+        match connect(config, &host, clock.clone(), topics.clone()).await {
+            Ok(nt) => return Ok((nt, host)),
+            Err(err) => last = anyhow!("{}: {}", host, err),
+        }
     }
 
-    fn send(&mut self, uid: u32, ty: u32, val: impl Serialize) -> Result<()> {
-        let buf = rmp_serde::to_vec(&(uid, now() + self.delta, ty, val))?;
-        self.ws.send(Message::Binary(buf.into()))?;
+    Err(last)
+}
+
+async fn connect(config: &NtConfig, host: &str, clock: Arc<Clock>, topics: Arc<Topics>) -> Result<NT> {
+    let mut nt = NT::connect(config, host, clock, topics).await?;
+    let root = config.root();
 
-        Ok(())
+    for (name, schema) in SCHEMAS {
+        nt.set(
+            &format!("/.schema/struct:{}", name),
+            "structschema",
+            Raw(schema.as_bytes().to_vec()),
+        ).await?;
     }
+
+    let mut subs = config.subscribe.clone();
+    subs.push(format!("{}/control/", root));
+    nt.subscribe(&subs, true).await?;
+
+    Ok(nt)
 }
 
-pub async fn run(states: Vec<Arc<State>>, notify: Arc<Notify>) {
-    loop {
-        let mut nt = loop {
-            match init() {
-                Ok(nt) => break nt,
-                Err(err) => {
-                    println!("\rnt: {} [reason: {}]", "init failed".red(), err);
-                    thread::sleep(Duration::from_millis(2000));
-                }
-            }
-        };
+/// Subscribes to each camera's control prefix and to robot pose topics read
+/// by simulated cameras, picking up renames and sources switched to sim after
+/// connecting.
+async fn subscribe_new(nt: &mut NT, root: &str, states: &[Arc<State>], subscribed: &mut HashSet<String>) -> Result<()> {
+    let mut controls = Vec::new();
+    let mut poses = Vec::new();
 
-        println!("\rnt: {}", "connected".green());
+    for st in states {
+        let control = format!("{}/{}/control/", root, st.name());
+        if subscribed.insert(control.clone()) {
+            controls.push(control);
+        }
 
-        loop {
-            if let Err(err) = tick(&mut nt, &states) {
-                println!("\rnt: {} [reason: {}]", "tick failed".red(), err);
-                break;
+        if let Source::Sim(sim) = &st.config().server.source {
+            if subscribed.insert(sim.topic.clone()) {
+                poses.push(sim.topic.clone());
             }
-            notify.notified().await;
         }
     }
+
+    if !controls.is_empty() {
+        nt.subscribe(&controls, true).await?;
+    }
+    if !poses.is_empty() {
+        nt.subscribe(&poses, false).await?;
+    }
+
+    Ok(())
 }
 
-fn init() -> Result<NT> {
-    let mut nt = NT::new()?;
+async fn tick(
+    nt: &mut NT,
+    outputs: &[Output],
+    multitag: &MultitagConfig,
+    states: &[Arc<State>],
+    topics: &Topics,
+    recorder: &Recorder,
+    control: &mut Control,
+) -> Result<()> {
+    let offset = nt.clock.offset() as f64 / 1_000_000.0;
+    let root = nt.root.clone();
 
-    nt.publish("/dauntless/tags", UID_JSON, "json")?;
-    nt.publish("/dauntless/ids", UID_IDS, "int[]")?;
-    nt.publish("/dauntless/time", UID_TIME, "double")?;
+    for st in states {
+        let base = format!("{}/{}", root, st.name());
+        control.apply(&base, st, topics);
+    }
+    control.record(&root, recorder, topics);
 
-    Ok(nt)
+    nt.set(&format!("{}/recording", root), "boolean", recorder.active()).await?;
+
+    if outputs.contains(&Output::Dauntless) {
+        tick_dauntless(nt, states, offset).await?;
+        tick_multitag(nt, states, multitag, offset).await?;
+    }
+
+    if !outputs.iter().any(|o| matches!(o, Output::Photon | Output::Limelight)) {
+        return Ok(());
+    }
+
+    for st in states {
+        let view = View::new(st, offset);
+
+        if outputs.contains(&Output::Photon) {
+            photon::publish(nt, &st.name(), &view).await?;
+        }
+        if outputs.contains(&Output::Limelight) {
+            limelight::publish(nt, &st.name(), &view).await?;
+        }
+    }
+
+    Ok(())
 }
 
-fn tick(nt: &mut NT, states: &[Arc<State>]) -> Result<()> {
+async fn tick_dauntless(nt: &mut NT, states: &[Arc<State>], offset: f64) -> Result<()> {
     let (tags, ids): (Vec<CameraTag>, Vec<u32>) =
         states
             .iter()
@@ -121,21 +216,127 @@ fn tick(nt: &mut NT, states: &[Arc<State>]) -> Result<()> {
             .filter_map(|t| {
                 t.tag.id.map(|id| {
                     let mut tag = t;
-                    tag.time += nt.delta as f64 / 1_000_000.0;
+                    tag.time += offset;
                     (tag, id)
                 })
             })
             .unzip();
 
     let json = serde_json::to_string(&tags)?;
+    let time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs_f64();
+    let root = nt.root.clone();
+
+    nt.set(&format!("{}/tags", root), "json", json).await?;
+    nt.set(&format!("{}/ids", root), "int[]", ids).await?;
+    nt.set(&format!("{}/time", root), "double", time).await?;
+
+    for st in states {
+        let base = format!("{}/{}", root, st.name());
+        tick_camera(nt, st, &base, offset).await?;
+    }
+
+    Ok(())
+}
 
-    nt.send(UID_JSON, TYPE_JSON, json)?;
-    nt.send(UID_IDS, TYPE_INTLIST, ids)?;
-    nt.send(UID_TIME, TYPE_DOUBLE, SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs_f64())?;
+async fn tick_multitag(nt: &mut NT, states: &[Arc<State>], config: &MultitagConfig, offset: f64) -> Result<()> {
+    let Some(fused) = multitag::fuse(states, config.skew) else {
+        return Ok(());
+    };
+
+    let base = format!("{}/multitag", nt.root);
+
+    let mut buf = Vec::new();
+    Pose3d(fused.solution.pose).pack(&mut buf);
+
+    let ids: Vec<i64> = fused.solution.ids.iter().map(|&id| id as i64).collect();
+    let cameras: Vec<i64> = fused.cameras.iter().map(|&id| id as i64).collect();
+
+    nt.set(&format!("{}/pose", base), &structs::type_of::<Pose3d>(), Raw(buf)).await?;
+    nt.set(&format!("{}/error", base), "double", fused.solution.error).await?;
+    nt.set(&format!("{}/std_devs", base), "double[]", fused.std_devs).await?;
+    nt.set(&format!("{}/ids", base), "int[]", ids).await?;
+    nt.set(&format!("{}/cameras", base), "int[]", cameras).await?;
+    nt.set(&format!("{}/time", base), "double", fused.time + offset).await?;
 
     Ok(())
 }
 
-fn now() -> i64 {
-    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_micros() as i64
+async fn tick_camera(nt: &mut NT, st: &State, base: &str, offset: f64) -> Result<()> {
+    let (tags, pose, time, latency, ms, fps, seq, connected) = {
+        let data = st.data();
+
+        let tags: Vec<CameraTag> = data.tags.iter().filter(|t| t.tag.id.is_some()).copied().collect();
+        let connected = data.updated.is_some_and(|t| t.elapsed() < STALE_TIMEOUT);
+
+        (
+            tags,
+            data.pose.clone(),
+            data.time.map(|t| t + offset).unwrap_or(0.0),
+            (data.capture_latency.unwrap_or(0.0), data.latency.unwrap_or(0.0)),
+            data.ms.unwrap_or(0.0) as f64,
+            data.fps.unwrap_or(0.0) as f64,
+            data.seq as i64,
+            connected,
+        )
+    };
+
+    let config = st.config().clone();
+
+    let poses: Vec<Transform> = tags.iter().map(|t| t.poses.best).collect();
+    let alts: Vec<Transform> = tags.iter().map(|t| t.poses.alt).collect();
+    let robot_poses: Vec<Transform> = tags.iter().map(|t| t.robot).collect();
+    let ids: Vec<i64> = tags.iter().filter_map(|t| t.tag.id).map(|id| id as i64).collect();
+    let ambiguity: Vec<f64> = tags.iter().map(|t| t.poses.ambiguity).collect();
+    let errors: Vec<f64> = tags.iter().map(|t| t.poses.best_error).collect();
+    let alt_errors: Vec<f64> = tags.iter().map(|t| t.poses.alt_error.min(f64::MAX)).collect();
+
+    let tracks: Vec<i64> = tags.iter().map(|t| t.track.map_or(-1, |tr| tr.id as i64)).collect();
+    let ages: Vec<f64> = tags.iter().map(|t| t.track.map_or(0.0, |tr| tr.age)).collect();
+    let velocities: Vec<f64> =
+        tags
+            .iter()
+            .flat_map(|t| {
+                let v = t.track.map(|tr| tr.velocity).unwrap_or_default();
+                [v.x, v.y, v.z]
+            })
+            .collect();
+
+    let transforms = structs::array_type_of::<Transform>();
+
+    nt.set(&format!("{}/tags", base), &transforms, Raw(structs::pack_all(&poses))).await?;
+    nt.set(&format!("{}/alt_tags", base), &transforms, Raw(structs::pack_all(&alts))).await?;
+    nt.set(&format!("{}/robot_tags", base), &transforms, Raw(structs::pack_all(&robot_poses))).await?;
+    nt.set(&format!("{}/ambiguity", base), "double[]", ambiguity).await?;
+    nt.set(&format!("{}/errors", base), "double[]", errors).await?;
+    nt.set(&format!("{}/alt_errors", base), "double[]", alt_errors).await?;
+    nt.set(&format!("{}/track_ids", base), "int[]", tracks).await?;
+    nt.set(&format!("{}/track_ages", base), "double[]", ages).await?;
+    nt.set(&format!("{}/track_velocities", base), "double[]", velocities).await?;
+    nt.set(&format!("{}/ids", base), "int[]", ids).await?;
+    nt.set(&format!("{}/time", base), "double", time).await?;
+    nt.set(&format!("{}/ms", base), "double", ms).await?;
+    nt.set(&format!("{}/capture_latency", base), "double", latency.0).await?;
+    nt.set(&format!("{}/latency", base), "double", latency.1).await?;
+    nt.set(&format!("{}/fps", base), "double", fps).await?;
+    nt.set(&format!("{}/seq", base), "int", seq).await?;
+    nt.set(&format!("{}/connected", base), "boolean", connected).await?;
+
+    if let Some(pose) = pose {
+        let mut buf = Vec::new();
+        Pose3d(pose.pose).pack(&mut buf);
+
+        let ids: Vec<i64> = pose.ids.iter().map(|&id| id as i64).collect();
+
+        nt.set(&format!("{}/pose", base), &structs::type_of::<Pose3d>(), Raw(buf)).await?;
+        nt.set(&format!("{}/pose_time", base), "double", pose.time + offset).await?;
+        nt.set(&format!("{}/pose_ids", base), "int[]", ids).await?;
+        nt.set(&format!("{}/pose_std_devs", base), "double[]", pose.std_devs).await?;
+    }
+
+    nt.set(&format!("{}/config", base), "json", serde_json::to_string(&config)?).await?;
+    nt.set(&format!("{}/pipeline", base), "int", config.pipeline as i64).await?;
+    nt.set(&format!("{}/enabled", base), "boolean", config.server.enabled).await?;
+    nt.set(&format!("{}/driver_mode", base), "boolean", config.server.driver_mode).await?;
+
+    Ok(())
 }
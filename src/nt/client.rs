@@ -0,0 +1,276 @@
+use super::clock::{now, Clock};
+use super::topics::{self, Topics, Value, TYPE_INT};
+
+use crate::config::NtConfig;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as Json;
+
+use rocket::futures::{SinkExt, StreamExt};
+use rocket::futures::stream::{SplitSink, SplitStream};
+
+use tokio::net::TcpStream;
+use tokio::sync::mpsc::{self, error::TrySendError, Receiver, Sender};
+use tokio::task::JoinHandle;
+use tokio::time::{self, timeout};
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(500);
+const SYNC_TIMEOUT: Duration = Duration::from_millis(1000);
+
+const PING_INTERVAL: Duration = Duration::from_millis(1000);
+const KEEPALIVE_TIMEOUT: Duration = Duration::from_millis(3000);
+
+const CTRL_QUEUE: usize = 64;
+const VALUE_QUEUE: usize = 256;
+
+pub struct NT {
+    pub root: String,
+    pub clock: Arc<Clock>,
+    ctrl: Sender<Message>,
+    values: Sender<Message>,
+    pubs: HashMap<String, (u32, u32)>,
+    next_subuid: u32,
+    reader: JoinHandle<Result<()>>,
+    writer: JoinHandle<Result<()>>,
+}
+
+#[derive(Deserialize)]
+struct Command {
+    method: String,
+    params: Json,
+}
+
+#[derive(Deserialize)]
+struct Announce {
+    name: String,
+    id: i64,
+    #[serde(rename = "type")]
+    ty: String,
+}
+
+#[derive(Deserialize)]
+struct Unannounce {
+    id: i64,
+}
+
+impl NT {
+    pub async fn connect(
+        config: &NtConfig,
+        host: &str,
+        clock: Arc<Clock>,
+        topics: Arc<Topics>,
+    ) -> Result<Self> {
+        let mut req = config.url(host).into_client_request()?;
+        req.headers_mut().insert(
+            "Sec-WebSocket-Protocol",
+            "v4.1.networktables.first.wpi.edu".parse()?,
+        );
+
+        let (ws, _) =
+            timeout(CONNECT_TIMEOUT, tokio_tungstenite::connect_async(req))
+                .await
+                .map_err(|_| anyhow!("connect timed out"))??;
+
+        let (mut sink, mut stream) = ws.split();
+
+        sink.send(sync_msg()?).await?;
+
+        let buf =
+            timeout(SYNC_TIMEOUT, first_binary(&mut stream))
+                .await
+                .map_err(|_| anyhow!("time sync timed out"))??;
+
+        let (_, server, _, sent): (i64, i64, u32, i64) = rmp_serde::from_slice(&buf)?;
+
+        clock.reset();
+        clock.add(sent, server, now());
+
+        let seen = Arc::new(Mutex::new(Instant::now()));
+
+        let (ctrl, ctrl_rx) = mpsc::channel(CTRL_QUEUE);
+        let (values, values_rx) = mpsc::channel(VALUE_QUEUE);
+
+        let writer = tokio::spawn(write(sink, ctrl_rx, values_rx, seen.clone()));
+        let reader = tokio::spawn(read(stream, topics, clock.clone(), seen));
+
+        Ok(Self {
+            root: config.root(),
+            clock,
+            ctrl,
+            values,
+            pubs: HashMap::new(),
+            next_subuid: 1,
+            reader,
+            writer,
+        })
+    }
+
+    pub async fn closed(&mut self) -> Result<()> {
+        let res = tokio::select! {
+            res = &mut self.reader => res,
+            res = &mut self.writer => res,
+        };
+
+        res?
+    }
+
+    pub async fn subscribe(&mut self, topics: &[String], prefix: bool) -> Result<u32> {
+        let subuid = self.next_subuid;
+        self.next_subuid += 1;
+
+        let msg = serde_json::json!([{
+            "method": "subscribe",
+            "params": {
+                "topics": topics,
+                "subuid": subuid,
+                "options": { "prefix": prefix },
+            },
+        }]);
+        self.ctrl.send(Message::Text(msg.to_string().into())).await?;
+
+        Ok(subuid)
+    }
+
+    pub async fn publish(&mut self, topic: &str, ty: &str) -> Result<(u32, u32)> {
+        if let Some(&publ) = self.pubs.get(topic) {
+            return Ok(publ);
+        }
+
+        let uid = self.pubs.len() as u32 + 1;
+        let publ = (uid, topics::type_id(ty));
+
+        let msg = serde_json::json!([{
+            "method": "publish",
+            "params": {
+                "name": topic,
+                "pubuid": uid,
+                "type": ty,
+                "properties": {},
+            },
+        }]);
+        self.ctrl.send(Message::Text(msg.to_string().into())).await?;
+
+        self.pubs.insert(topic.to_string(), publ);
+        Ok(publ)
+    }
+
+    pub async fn set(&mut self, topic: &str, ty: &str, val: impl Serialize) -> Result<()> {
+        let (uid, id) = self.publish(topic, ty).await?;
+
+        let buf = rmp_serde::to_vec(&(uid, self.clock.server_time(), id, val))?;
+
+        match self.values.try_send(Message::Binary(buf.into())) {
+            Ok(()) | Err(TrySendError::Full(_)) => Ok(()),
+            Err(TrySendError::Closed(_)) => bail!("connection closed"),
+        }
+    }
+}
+
+impl Drop for NT {
+    fn drop(&mut self) {
+        self.reader.abort();
+        self.writer.abort();
+    }
+}
+
+async fn write(
+    mut sink: SplitSink<Socket, Message>,
+    mut ctrl: Receiver<Message>,
+    mut values: Receiver<Message>,
+    seen: Arc<Mutex<Instant>>,
+) -> Result<()> {
+    let mut ping = time::interval(PING_INTERVAL);
+
+    loop {
+        let msg = tokio::select! {
+            biased;
+
+            Some(msg) = ctrl.recv() => msg,
+            Some(msg) = values.recv() => msg,
+            _ = ping.tick() => {
+                if seen.lock().unwrap().elapsed() > KEEPALIVE_TIMEOUT {
+                    bail!("keepalive timed out");
+                }
+
+                sink.send(Message::Ping(Default::default())).await?;
+                sync_msg()?
+            }
+        };
+
+        sink.send(msg).await?;
+    }
+}
+
+async fn read(
+    mut stream: SplitStream<Socket>,
+    topics: Arc<Topics>,
+    clock: Arc<Clock>,
+    seen: Arc<Mutex<Instant>>,
+) -> Result<()> {
+    while let Some(msg) = stream.next().await {
+        *seen.lock().unwrap() = Instant::now();
+
+        match msg? {
+            Message::Text(text) => {
+                let cmds: Vec<Command> = serde_json::from_str(&text)?;
+                for cmd in cmds {
+                    handle(cmd, &topics)?;
+                }
+            }
+            Message::Binary(buf) => {
+                for update in topics::parse_updates(&buf)? {
+                    if update.id >= 0 {
+                        topics.update(update);
+                    } else if let Some(Value::Int(sent)) = update.value {
+                        clock.add(sent, update.time, now());
+                    }
+                }
+            }
+            Message::Close(_) => break,
+            _ => {}
+        }
+    }
+
+    bail!("connection closed")
+}
+
+async fn first_binary(stream: &mut SplitStream<Socket>) -> Result<Vec<u8>> {
+    while let Some(msg) = stream.next().await {
+        if let Message::Binary(buf) = msg? {
+            return Ok(buf.to_vec());
+        }
+    }
+
+    bail!("connection closed")
+}
+
+fn handle(cmd: Command, topics: &Topics) -> Result<()> {
+    match cmd.method.as_str() {
+        "announce" => {
+            let ann: Announce = serde_json::from_value(cmd.params)?;
+            topics.announce(ann.id, &ann.name, &ann.ty);
+        }
+        "unannounce" => {
+            let ann: Unannounce = serde_json::from_value(cmd.params)?;
+            topics.unannounce(ann.id);
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+fn sync_msg() -> Result<Message> {
+    let buf = rmp_serde::to_vec(&(-1i64, 0i64, TYPE_INT, now()))?;
+    Ok(Message::Binary(buf.into()))
+}
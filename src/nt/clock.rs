@@ -0,0 +1,55 @@
+use std::collections::VecDeque;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+const WINDOW: usize = 10;
+
+#[derive(Clone, Copy, Serialize)]
+pub struct Sample {
+    pub offset: i64,
+    pub rtt: i64,
+}
+
+#[derive(Default)]
+pub struct Clock {
+    samples: Mutex<VecDeque<Sample>>,
+}
+
+impl Clock {
+    pub fn add(&self, sent: i64, server: i64, recv: i64) {
+        let rtt = recv - sent;
+        let offset = server + rtt / 2 - recv;
+
+        let mut samples = self.samples();
+        if samples.len() == WINDOW {
+            samples.pop_front();
+        }
+        samples.push_back(Sample { offset, rtt });
+    }
+
+    pub fn best(&self) -> Option<Sample> {
+        self.samples().iter().min_by_key(|s| s.rtt).copied()
+    }
+
+    pub fn offset(&self) -> i64 {
+        self.best().map(|s| s.offset).unwrap_or(0)
+    }
+
+    pub fn server_time(&self) -> i64 {
+        now() + self.offset()
+    }
+
+    pub fn reset(&self) {
+        self.samples().clear();
+    }
+
+    fn samples(&self) -> MutexGuard<'_, VecDeque<Sample>> {
+        self.samples.lock().unwrap()
+    }
+}
+
+pub fn now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_micros() as i64
+}
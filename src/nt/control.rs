@@ -0,0 +1,86 @@
+use super::topics::{Topics, Value};
+
+use crate::record::Recorder;
+use crate::state::State;
+
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+
+use colored::Colorize;
+
+#[derive(Default)]
+pub struct Control {
+    seen: HashMap<String, i64>,
+}
+
+impl Control {
+    pub fn apply(&mut self, base: &str, st: &State, topics: &Topics) {
+        let topic = |name: &str| format!("{}/control/{}", base, name);
+
+        let enable = self.fresh(topics, &topic("enable"));
+        let pipeline = self.fresh(topics, &topic("pipeline"));
+        let hyst_high = self.fresh(topics, &topic("hyst_high"));
+        let hyst_low = self.fresh(topics, &topic("hyst_low"));
+        let driver_mode = self.fresh(topics, &topic("driver_mode"));
+        let snapshot = self.fresh(topics, &topic("snapshot"));
+
+        {
+            let mut config = st.config();
+
+            if let Some(Value::Bool(enable)) = enable {
+                config.server.enabled = enable;
+            }
+            if let Some(pipeline) = pipeline.as_ref().and_then(as_f64) {
+                match usize::try_from(pipeline as i64) {
+                    Ok(index) if config.select(index) => {}
+                    _ => println!("\rnt: {} [reason: no pipeline {}]", "pipeline ignored".red(), pipeline),
+                }
+            }
+            if let Some(hyst_high) = hyst_high.as_ref().and_then(as_f64) {
+                config.detector.hyst_high = hyst_high as _;
+            }
+            if let Some(hyst_low) = hyst_low.as_ref().and_then(as_f64) {
+                config.detector.hyst_low = hyst_low as _;
+            }
+            if let Some(Value::Bool(driver_mode)) = driver_mode {
+                config.server.driver_mode = driver_mode;
+            }
+        }
+
+        if let Some(Value::Bool(true)) = snapshot {
+            st.snapshot.store(true, Ordering::Relaxed);
+        }
+    }
+
+    pub fn record(&mut self, root: &str, recorder: &Recorder, topics: &Topics) {
+        match self.fresh(topics, &format!("{}/control/record", root)) {
+            Some(Value::Bool(true)) => {
+                if let Err(err) = recorder.start() {
+                    println!("\rnt: {} [reason: {}]", "record failed".red(), err);
+                }
+            }
+            Some(Value::Bool(false)) => recorder.stop(),
+            _ => {}
+        }
+    }
+
+    fn fresh(&mut self, topics: &Topics, name: &str) -> Option<Value> {
+        let topic = topics.get(name)?;
+
+        if self.seen.get(name) == Some(&topic.time) {
+            return None;
+        }
+        self.seen.insert(name.to_string(), topic.time);
+
+        topic.value
+    }
+}
+
+fn as_f64(value: &Value) -> Option<f64> {
+    match value {
+        Value::Double(v) => Some(*v),
+        Value::Float(v) => Some(*v as f64),
+        Value::Int(v) => Some(*v as f64),
+        _ => None,
+    }
+}
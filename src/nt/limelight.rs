@@ -0,0 +1,113 @@
+use super::client::NT;
+use super::targets::{Target, View};
+
+use anyhow::Result;
+use serde_json::json;
+
+pub async fn publish(nt: &mut NT, name: &str, view: &View) -> Result<()> {
+    let base = format!("/limelight-{}", name);
+
+    let now = nt.clock.server_time() as f64 / 1_000_000.0;
+    let tl = view.ms;
+    let cl = ((now - view.time) * 1000.0 - tl).max(0.0);
+
+    let best = view.targets.first();
+
+    let tv = if best.is_some() { 1.0 } else { 0.0 };
+    let (tx, ty, ta, tid) =
+        best
+            .map(|t| (t.yaw, t.pitch, t.area, t.id as f64))
+            .unwrap_or((0.0, 0.0, 0.0, -1.0));
+
+    let botpose = bot_pose(view, tl + cl);
+
+    let fiducials: Vec<_> =
+        view.targets
+            .iter()
+            .map(|t| json!({
+                "fID": t.id,
+                "fam": "36H11C",
+                "tx": t.yaw,
+                "ty": t.pitch,
+                "ta": t.area,
+                "t6t_cs": camera_space(t),
+                "t6t_rs": robot_space(t),
+                "pts": t.corners.iter().map(|c| [c.0, c.1]).collect::<Vec<_>>(),
+            }))
+            .collect();
+
+    let json = json!({
+        "Results": {
+            "pID": view.pipeline,
+            "tl": tl,
+            "cl": cl,
+            "ts": view.time * 1000.0,
+            "v": tv as i32,
+            "botpose_wpiblue": botpose,
+            "Fiducial": fiducials,
+        },
+    });
+
+    nt.set(&format!("{}/tv", base), "double", tv).await?;
+    nt.set(&format!("{}/tx", base), "double", tx).await?;
+    nt.set(&format!("{}/ty", base), "double", ty).await?;
+    nt.set(&format!("{}/ta", base), "double", ta).await?;
+    nt.set(&format!("{}/tid", base), "double", tid).await?;
+    nt.set(&format!("{}/tl", base), "double", tl).await?;
+    nt.set(&format!("{}/cl", base), "double", cl).await?;
+    nt.set(&format!("{}/getpipe", base), "double", view.pipeline as f64).await?;
+    nt.set(&format!("{}/botpose_wpiblue", base), "double[]", botpose).await?;
+    nt.set(
+        &format!("{}/targetpose_cameraspace", base),
+        "double[]",
+        best.map(camera_space).unwrap_or_default(),
+    ).await?;
+    nt.set(
+        &format!("{}/targetpose_robotspace", base),
+        "double[]",
+        best.map(robot_space).unwrap_or_default(),
+    ).await?;
+    nt.set(&format!("{}/json", base), "string", json.to_string()).await?;
+
+    Ok(())
+}
+
+fn bot_pose(view: &View, latency: f64) -> [f64; 11] {
+    let Some(pose) = &view.pose else {
+        return [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, latency, 0.0, 0.0, 0.0, 0.0];
+    };
+
+    let t = pose.pose.translation;
+    let (roll, pitch, yaw) = pose.pose.rotation.rpy();
+
+    let used: Vec<&Target> = view.targets.iter().filter(|t| pose.ids.contains(&t.id)).collect();
+    let n = used.len().max(1) as f64;
+
+    let dist = used.iter().map(|t| t.transform.translation.norm()).sum::<f64>() / n;
+    let area = used.iter().map(|t| t.area).sum::<f64>() / n;
+    let span =
+        used
+            .iter()
+            .flat_map(|a| used.iter().map(move |b| (a.transform.translation - b.transform.translation).norm()))
+            .fold(0.0, f64::max);
+
+    [
+        t.x, t.y, t.z,
+        roll.to_degrees(), pitch.to_degrees(), yaw.to_degrees(),
+        latency, pose.ids.len() as f64, span, dist, area,
+    ]
+}
+
+fn camera_space(target: &Target) -> [f64; 6] {
+    let t = target.transform.translation;
+    let (roll, pitch, yaw) = target.transform.rotation.rpy();
+
+    [-t.y, -t.z, t.x, roll.to_degrees(), pitch.to_degrees(), yaw.to_degrees()]
+}
+
+fn robot_space(target: &Target) -> [f64; 6] {
+    let t = target.robot.translation;
+    let (roll, pitch, yaw) = target.robot.rotation.rpy();
+
+    [t.x, t.y, t.z, roll.to_degrees(), pitch.to_degrees(), yaw.to_degrees()]
+}
@@ -0,0 +1,434 @@
+use super::clock::now;
+use super::topics::{self, Value, TYPE_INT};
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as Json};
+
+use rocket::futures::{SinkExt, StreamExt};
+
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::http::StatusCode;
+
+const PROTOCOL: &str = "v4.1.networktables.first.wpi.edu";
+
+/// Values kept per topic. The server publishes every topic each frame, so
+/// history is capped rather than growing for the life of the process.
+const HISTORY: usize = 64;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct MockTopic {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: String,
+    pub id: i64,
+    pub values: VecDeque<(i64, Value)>,
+}
+
+impl MockTopic {
+    pub fn value(&self) -> Option<&Value> {
+        self.values.back().map(|(_, v)| v)
+    }
+}
+
+struct Sub {
+    uid: i64,
+    topics: Vec<String>,
+    prefix: bool,
+}
+
+impl Sub {
+    fn matches(&self, name: &str) -> bool {
+        self.topics
+            .iter()
+            .any(|t| if self.prefix { name.starts_with(t.as_str()) } else { name == t })
+    }
+}
+
+struct Client {
+    tx: UnboundedSender<Message>,
+    subs: Vec<Sub>,
+    pubs: HashMap<i64, String>,
+    announced: HashSet<String>,
+}
+
+#[derive(Default)]
+struct Inner {
+    topics: Mutex<HashMap<String, MockTopic>>,
+    clients: Mutex<HashMap<u64, Client>>,
+    next_topic: AtomicI64,
+    next_client: AtomicU64,
+    notify: Notify,
+}
+
+#[derive(Deserialize)]
+struct Command {
+    method: String,
+    params: Json,
+}
+
+#[derive(Deserialize)]
+struct Publish {
+    name: String,
+    pubuid: i64,
+    #[serde(rename = "type")]
+    ty: String,
+}
+
+#[derive(Deserialize)]
+struct Unpublish {
+    pubuid: i64,
+}
+
+#[derive(Deserialize)]
+struct Subscribe {
+    topics: Vec<String>,
+    subuid: i64,
+    #[serde(default)]
+    options: Options,
+}
+
+#[derive(Default, Deserialize)]
+struct Options {
+    #[serde(default)]
+    prefix: bool,
+}
+
+#[derive(Deserialize)]
+struct Unsubscribe {
+    subuid: i64,
+}
+
+pub struct MockServer {
+    addr: SocketAddr,
+    inner: Arc<Inner>,
+    task: JoinHandle<()>,
+}
+
+impl MockServer {
+    pub async fn bind(addr: impl ToSocketAddrs) -> Result<Self> {
+        let listener = TcpListener::bind(addr).await?;
+        let addr = listener.local_addr()?;
+
+        let inner = Arc::new(Inner::default());
+        let task = tokio::spawn(accept(listener, inner.clone()));
+
+        Ok(Self { addr, inner, task })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn topics(&self) -> Vec<MockTopic> {
+        let mut topics: Vec<MockTopic> = self.inner.topics().values().cloned().collect();
+        topics.sort_by(|a, b| a.name.cmp(&b.name));
+        topics
+    }
+
+    pub fn topic(&self, name: &str) -> Option<MockTopic> {
+        self.inner.topics().get(name).cloned()
+    }
+
+    pub fn value(&self, name: &str) -> Option<Value> {
+        self.topic(name).and_then(|t| t.value().cloned())
+    }
+
+    pub async fn wait_for(&self, name: &str) -> Value {
+        loop {
+            let notified = self.inner.notify.notified();
+
+            if let Some(value) = self.value(name) {
+                return value;
+            }
+
+            notified.await;
+        }
+    }
+
+    pub fn inject(&self, name: &str, ty: &str, value: Value) {
+        self.inner.set(None, name, ty, now(), value);
+    }
+
+    /// Drops recorded history, keeping each topic's latest value.
+    pub fn clear(&self) {
+        for topic in self.inner.topics().values_mut() {
+            let last = topic.values.pop_back();
+            topic.values = last.into_iter().collect();
+        }
+    }
+}
+
+/// Converts a JSON value to an NT value of type `ty`, for injecting topics
+/// over the API.
+pub fn parse_value(ty: &str, value: Json) -> Result<Value> {
+    let value = match topics::type_id(ty) {
+        topics::TYPE_BOOL => Value::Bool(serde_json::from_value(value)?),
+        topics::TYPE_DOUBLE => Value::Double(serde_json::from_value(value)?),
+        topics::TYPE_INT => Value::Int(serde_json::from_value(value)?),
+        topics::TYPE_FLOAT => Value::Float(serde_json::from_value(value)?),
+        topics::TYPE_STRING => match value {
+            Json::String(s) => Value::String(s),
+            other => Value::String(other.to_string()),
+        },
+        topics::TYPE_BOOLLIST => Value::BoolList(serde_json::from_value(value)?),
+        topics::TYPE_DOUBLELIST => Value::DoubleList(serde_json::from_value(value)?),
+        topics::TYPE_INTLIST => Value::IntList(serde_json::from_value(value)?),
+        topics::TYPE_FLOATLIST => Value::FloatList(serde_json::from_value(value)?),
+        topics::TYPE_STRINGLIST => Value::StringList(serde_json::from_value(value)?),
+        _ => match value {
+            Json::Array(bytes) => Value::Raw(topics::Raw(
+                bytes
+                    .iter()
+                    .map(|b| b.as_u64().and_then(|b| u8::try_from(b).ok()).ok_or_else(|| anyhow!("raw values are byte arrays")))
+                    .collect::<Result<_>>()?,
+            )),
+            _ => bail!("raw values are byte arrays"),
+        },
+    };
+
+    Ok(value)
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl Inner {
+    fn topics(&self) -> MutexGuard<'_, HashMap<String, MockTopic>> {
+        self.topics.lock().unwrap()
+    }
+
+    fn clients(&self) -> MutexGuard<'_, HashMap<u64, Client>> {
+        self.clients.lock().unwrap()
+    }
+
+    fn announce(&self, source: Option<u64>, name: &str, ty: &str) -> i64 {
+        let id = {
+            let mut topics = self.topics();
+
+            topics
+                .entry(name.to_string())
+                .or_insert_with(|| MockTopic {
+                    name: name.to_string(),
+                    ty: ty.to_string(),
+                    id: self.next_topic.fetch_add(1, Ordering::Relaxed),
+                    values: VecDeque::new(),
+                })
+                .id
+        };
+
+        for (cid, client) in self.clients().iter_mut() {
+            if Some(*cid) == source || client.subs.iter().any(|s| s.matches(name)) {
+                client.announce(name, ty, id);
+            }
+        }
+
+        self.notify.notify_waiters();
+        id
+    }
+
+    fn set(&self, source: Option<u64>, name: &str, ty: &str, time: i64, value: Value) {
+        let id = self.announce(source, name, ty);
+
+        if let Some(topic) = self.topics().get_mut(name) {
+            if topic.values.len() == HISTORY {
+                topic.values.pop_front();
+            }
+            topic.values.push_back((time, value.clone()));
+        }
+
+        for (cid, client) in self.clients().iter_mut() {
+            if Some(*cid) != source && client.subs.iter().any(|s| s.matches(name)) {
+                client.send_value(id, time, topics::type_id(ty), &value);
+            }
+        }
+
+        self.notify.notify_waiters();
+    }
+
+    fn handle(&self, cid: u64, cmd: Command) -> Result<()> {
+        match cmd.method.as_str() {
+            "publish" => {
+                let publ: Publish = serde_json::from_value(cmd.params)?;
+
+                if let Some(client) = self.clients().get_mut(&cid) {
+                    client.pubs.insert(publ.pubuid, publ.name.clone());
+                }
+                self.announce(Some(cid), &publ.name, &publ.ty);
+            }
+            "unpublish" => {
+                let publ: Unpublish = serde_json::from_value(cmd.params)?;
+
+                if let Some(client) = self.clients().get_mut(&cid) {
+                    client.pubs.remove(&publ.pubuid);
+                }
+            }
+            "subscribe" => {
+                let sub: Subscribe = serde_json::from_value(cmd.params)?;
+                let sub = Sub { uid: sub.subuid, topics: sub.topics, prefix: sub.options.prefix };
+
+                let topics = self.topics();
+                let mut clients = self.clients();
+
+                if let Some(client) = clients.get_mut(&cid) {
+                    for topic in topics.values().filter(|t| sub.matches(&t.name)) {
+                        client.announce(&topic.name, &topic.ty, topic.id);
+
+                        if let Some((time, value)) = topic.values.back() {
+                            client.send_value(topic.id, *time, topics::type_id(&topic.ty), value);
+                        }
+                    }
+
+                    client.subs.push(sub);
+                }
+            }
+            "unsubscribe" => {
+                let sub: Unsubscribe = serde_json::from_value(cmd.params)?;
+
+                if let Some(client) = self.clients().get_mut(&cid) {
+                    client.subs.retain(|s| s.uid != sub.subuid);
+                }
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn update(&self, cid: u64, buf: &[u8]) -> Result<()> {
+        for update in topics::parse_updates(buf)? {
+            if update.id == -1 {
+                if let (Some(value), Some(client)) = (update.value, self.clients().get(&cid)) {
+                    let buf = rmp_serde::to_vec(&(-1i64, now(), TYPE_INT, value))?;
+                    let _ = client.tx.send(Message::Binary(buf.into()));
+                }
+                continue;
+            }
+
+            let name = self.clients().get(&cid).and_then(|c| c.pubs.get(&update.id).cloned());
+            let ty = name.as_ref().and_then(|n| self.topics().get(n).map(|t| t.ty.clone()));
+
+            if let (Some(name), Some(ty), Some(value)) = (name, ty, update.value) {
+                self.set(Some(cid), &name, &ty, update.time, value);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Client {
+    fn announce(&mut self, name: &str, ty: &str, id: i64) {
+        if !self.announced.insert(name.to_string()) {
+            return;
+        }
+
+        let msg = json!([{
+            "method": "announce",
+            "params": {
+                "name": name,
+                "id": id,
+                "type": ty,
+                "properties": {},
+            },
+        }]);
+        let _ = self.tx.send(Message::Text(msg.to_string().into()));
+    }
+
+    fn send_value(&self, id: i64, time: i64, ty: u32, value: &Value) {
+        if let Ok(buf) = rmp_serde::to_vec(&(id, time, ty, value)) {
+            let _ = self.tx.send(Message::Binary(buf.into()));
+        }
+    }
+}
+
+async fn accept(listener: TcpListener, inner: Arc<Inner>) {
+    while let Ok((stream, _)) = listener.accept().await {
+        let inner = inner.clone();
+
+        tokio::spawn(async move {
+            if let Err(err) = serve(stream, &inner).await {
+                println!("\rmock: client error [reason: {}]", err);
+            }
+        });
+    }
+}
+
+async fn serve(stream: TcpStream, inner: &Inner) -> Result<()> {
+    let ws = tokio_tungstenite::accept_hdr_async(stream, negotiate).await?;
+    let (mut sink, mut stream) = ws.split();
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let cid = inner.next_client.fetch_add(1, Ordering::Relaxed);
+
+    inner.clients().insert(cid, Client {
+        tx,
+        subs: Vec::new(),
+        pubs: HashMap::new(),
+        announced: HashSet::new(),
+    });
+
+    let writer = tokio::spawn(async move {
+        while let Some(msg) = rx.recv().await {
+            if sink.send(msg).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let res = async {
+        while let Some(msg) = stream.next().await {
+            match msg? {
+                Message::Text(text) => {
+                    let cmds: Vec<Command> = serde_json::from_str(&text)?;
+                    for cmd in cmds {
+                        inner.handle(cid, cmd)?;
+                    }
+                }
+                Message::Binary(buf) => inner.update(cid, &buf)?,
+                Message::Close(_) => break,
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }.await;
+
+    inner.clients().remove(&cid);
+    writer.abort();
+
+    res
+}
+
+#[allow(clippy::result_large_err)]
+fn negotiate(req: &Request, mut res: Response) -> Result<Response, ErrorResponse> {
+    let supported =
+        req.headers()
+            .get_all("Sec-WebSocket-Protocol")
+            .iter()
+            .filter_map(|h| h.to_str().ok())
+            .flat_map(|h| h.split(','))
+            .any(|p| p.trim() == PROTOCOL);
+
+    if !supported {
+        let mut err = ErrorResponse::new(Some("unsupported subprotocol".into()));
+        *err.status_mut() = StatusCode::BAD_REQUEST;
+        return Err(err);
+    }
+
+    res.headers_mut().insert("Sec-WebSocket-Protocol", PROTOCOL.parse().unwrap());
+    Ok(res)
+}
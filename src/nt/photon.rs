@@ -0,0 +1,112 @@
+use super::client::NT;
+use super::targets::{Target, View};
+use super::topics::Raw;
+
+use crate::geom::Transform;
+
+use anyhow::Result;
+
+const MAX_FIDUCIALS: usize = 32;
+
+pub async fn publish(nt: &mut NT, name: &str, view: &View) -> Result<()> {
+    let base = format!("/photonvision/{}", name);
+
+    let now = nt.clock.server_time();
+    let capture = (view.time * 1_000_000.0) as i64;
+    let latency = (now - capture) as f64 / 1000.0;
+
+    nt.set(&format!("{}/rawBytes", base), "rawBytes", Raw(pack(view, capture, now))).await?;
+    nt.set(&format!("{}/latencyMillis", base), "double", latency).await?;
+    nt.set(&format!("{}/hasTarget", base), "boolean", !view.targets.is_empty()).await?;
+    nt.set(&format!("{}/heartbeat", base), "int", view.seq as i64).await?;
+    nt.set(&format!("{}/driverMode", base), "boolean", view.driver_mode).await?;
+
+    if let Some(best) = view.targets.first() {
+        let Transform { translation: t, rotation: r } = best.transform;
+
+        nt.set(&format!("{}/targetYaw", base), "double", best.yaw).await?;
+        nt.set(&format!("{}/targetPitch", base), "double", best.pitch).await?;
+        nt.set(&format!("{}/targetArea", base), "double", best.area).await?;
+        nt.set(&format!("{}/targetSkew", base), "double", 0.0).await?;
+        nt.set(&format!("{}/targetPose", base), "double[]", [t.x, t.y, t.z, r.w, r.x, r.y, r.z]).await?;
+    }
+
+    Ok(())
+}
+
+/// Packs a `PhotonPipelineResult` using PhotonLib's 2024 big-endian packet layout.
+fn pack(view: &View, capture: i64, publish: i64) -> Vec<u8> {
+    let mut buf = Vec::new();
+
+    buf.extend_from_slice(&(view.seq as i64).to_be_bytes());
+    buf.extend_from_slice(&capture.to_be_bytes());
+    buf.extend_from_slice(&publish.to_be_bytes());
+
+    buf.push(view.targets.len().min(u8::MAX as usize) as u8);
+    for target in view.targets.iter().take(u8::MAX as usize) {
+        pack_target(&mut buf, target);
+    }
+
+    match &view.multitag {
+        Some(sol) => {
+            buf.push(1);
+            pack_transform(&mut buf, &sol.pose);
+            pack_transform(&mut buf, &sol.pose);
+
+            for v in [sol.error, sol.error, 0.0] {
+                buf.extend_from_slice(&v.to_be_bytes());
+            }
+        }
+        None => buf.push(0),
+    }
+
+    let ids = view.multitag.as_ref().map(|s| s.ids.as_slice()).unwrap_or_default();
+    for i in 0..MAX_FIDUCIALS {
+        let id = ids.get(i).map_or(-1, |&id| id as i16);
+        buf.extend_from_slice(&id.to_be_bytes());
+    }
+
+    buf
+}
+
+fn pack_target(buf: &mut Vec<u8>, target: &Target) {
+    for v in [target.yaw, target.pitch, target.area, 0.0] {
+        buf.extend_from_slice(&v.to_be_bytes());
+    }
+    buf.extend_from_slice(&(target.id as i32).to_be_bytes());
+
+    pack_transform(buf, &target.transform);
+    pack_transform(buf, &target.alt);
+    buf.extend_from_slice(&target.ambiguity.to_be_bytes());
+
+    let [tl, tr, bl, br] = target.corners;
+
+    let (min_x, max_x) = bounds(target.corners.map(|c| c.0));
+    let (min_y, max_y) = bounds(target.corners.map(|c| c.1));
+
+    for (x, y) in [(min_x, max_y), (max_x, max_y), (max_x, min_y), (min_x, min_y)] {
+        pack_corner(buf, (x, y));
+    }
+
+    buf.push(4);
+    for corner in [bl, br, tr, tl] {
+        pack_corner(buf, corner);
+    }
+}
+
+fn pack_transform(buf: &mut Vec<u8>, transform: &Transform) {
+    let Transform { translation: t, rotation: r } = *transform;
+
+    for v in [t.x, t.y, t.z, r.w, r.x, r.y, r.z] {
+        buf.extend_from_slice(&v.to_be_bytes());
+    }
+}
+
+fn pack_corner(buf: &mut Vec<u8>, (x, y): (f64, f64)) {
+    buf.extend_from_slice(&x.to_be_bytes());
+    buf.extend_from_slice(&y.to_be_bytes());
+}
+
+fn bounds(vals: [f64; 4]) -> (f64, f64) {
+    vals.iter().fold((f64::MAX, f64::MIN), |(lo, hi), v| (lo.min(*v), hi.max(*v)))
+}
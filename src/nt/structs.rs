@@ -0,0 +1,99 @@
+use super::topics::{Raw, Value};
+
+use crate::geom::{Rotation, Transform, Translation};
+
+pub const SCHEMAS: &[(&str, &str)] = &[
+    ("Translation3d", "double x;double y;double z"),
+    ("Quaternion", "double w;double x;double y;double z"),
+    ("Rotation3d", "Quaternion q"),
+    ("Transform3d", "Translation3d translation;Rotation3d rotation"),
+    ("Pose3d", "Translation3d translation;Rotation3d rotation"),
+];
+
+pub trait Struct {
+    const NAME: &'static str;
+
+    fn pack(&self, buf: &mut Vec<u8>);
+}
+
+impl Struct for Translation {
+    const NAME: &'static str = "Translation3d";
+
+    fn pack(&self, buf: &mut Vec<u8>) {
+        for v in [self.x, self.y, self.z] {
+            buf.extend_from_slice(&v.to_le_bytes());
+        }
+    }
+}
+
+impl Struct for Rotation {
+    const NAME: &'static str = "Rotation3d";
+
+    fn pack(&self, buf: &mut Vec<u8>) {
+        for v in [self.w, self.x, self.y, self.z] {
+            buf.extend_from_slice(&v.to_le_bytes());
+        }
+    }
+}
+
+impl Struct for Transform {
+    const NAME: &'static str = "Transform3d";
+
+    fn pack(&self, buf: &mut Vec<u8>) {
+        self.translation.pack(buf);
+        self.rotation.pack(buf);
+    }
+}
+
+pub struct Pose3d(pub Transform);
+
+impl Struct for Pose3d {
+    const NAME: &'static str = "Pose3d";
+
+    fn pack(&self, buf: &mut Vec<u8>) {
+        self.0.pack(buf);
+    }
+}
+
+pub fn type_of<T: Struct>() -> String {
+    format!("struct:{}", T::NAME)
+}
+
+pub fn pack_all<T: Struct>(items: &[T]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    for item in items {
+        item.pack(&mut buf);
+    }
+    buf
+}
+
+pub fn array_type_of<T: Struct>() -> String {
+    format!("struct:{}[]", T::NAME)
+}
+
+/// Reads a robot pose from the ways WPILib publishes one: `Pose2d` and
+/// `Pose3d` structs, or the `double[]` of x, y and degrees from `Field2d`.
+pub fn unpack_pose(ty: &str, value: &Value) -> Option<Transform> {
+    match (ty, value) {
+        ("struct:Pose2d", Value::Raw(Raw(buf))) => {
+            let [x, y, theta] = doubles(buf)?;
+            Some(Transform::new(Translation::new(x, y, 0.0), Rotation::from_yaw(theta)))
+        }
+        ("struct:Pose3d", Value::Raw(Raw(buf))) => {
+            let [x, y, z, w, qx, qy, qz] = doubles(buf)?;
+            Some(Transform::new(Translation::new(x, y, z), Rotation { w, x: qx, y: qy, z: qz }.normalize()))
+        }
+        (_, Value::DoubleList(v)) if v.len() >= 3 && v.len() % 3 == 0 => {
+            Some(Transform::new(Translation::new(v[0], v[1], 0.0), Rotation::from_yaw(v[2].to_radians())))
+        }
+        _ => None,
+    }
+}
+
+fn doubles<const N: usize>(buf: &[u8]) -> Option<[f64; N]> {
+    if buf.len() < N * 8 {
+        return None;
+    }
+
+    Some(std::array::from_fn(|i| f64::from_le_bytes(buf[i * 8..i * 8 + 8].try_into().unwrap())))
+}
@@ -0,0 +1,97 @@
+use crate::field::RobotPose;
+use crate::geom::Transform;
+use crate::multitag;
+use crate::solve::Solution;
+use crate::state::State;
+
+pub struct Target {
+    pub id: u32,
+    pub yaw: f64,
+    pub pitch: f64,
+    pub area: f64,
+    pub corners: [(f64, f64); 4],
+    pub transform: Transform,
+    pub alt: Transform,
+    pub ambiguity: f64,
+    pub robot: Transform,
+}
+
+pub struct View {
+    pub seq: u64,
+    pub time: f64,
+    pub ms: f64,
+    pub pipeline: usize,
+    pub driver_mode: bool,
+    pub targets: Vec<Target>,
+    pub pose: Option<RobotPose>,
+    pub multitag: Option<Solution>,
+}
+
+impl View {
+    pub fn new(st: &State, offset: f64) -> Self {
+        let (res, pipeline, driver_mode) = {
+            let config = st.config();
+            (config.server.res, config.pipeline, config.server.driver_mode)
+        };
+
+        let intr = st.intrinsics();
+        let multitag = multitag::camera(st);
+        let data = st.data();
+
+        let mut targets: Vec<Target> =
+            data.tags
+                .iter()
+                .filter_map(|t| {
+                    let id = t.tag.id?;
+                    let corners = t.tag.corners.map(|(x, y)| (x as f64, y as f64));
+
+                    let center = (
+                        corners.iter().map(|c| c.0).sum::<f64>() / 4.0,
+                        corners.iter().map(|c| c.1).sum::<f64>() / 4.0,
+                    );
+                    let (yaw, pitch) = intr.angles(center);
+
+                    Some(Target {
+                        id,
+                        yaw: yaw.to_degrees(),
+                        pitch: pitch.to_degrees(),
+                        area: area(&corners) / (res.0 * res.1) as f64 * 100.0,
+                        corners,
+                        transform: t.poses.best,
+                        alt: t.poses.alt,
+                        ambiguity: t.poses.ambiguity,
+                        robot: t.robot,
+                    })
+                })
+                .collect();
+
+        targets.sort_by(|a, b| b.area.total_cmp(&a.area));
+
+        Self {
+            seq: data.seq,
+            time: data.time.map(|t| t + offset).unwrap_or(0.0),
+            ms: data.ms.unwrap_or(0.0) as f64,
+            pipeline,
+            driver_mode,
+            targets,
+            pose: data.pose.clone(),
+            multitag,
+        }
+    }
+}
+
+fn area(corners: &[(f64, f64); 4]) -> f64 {
+    let [tl, tr, bl, br] = *corners;
+    let poly = [tl, tr, br, bl];
+
+    let sum: f64 =
+        (0..4)
+            .map(|i| {
+                let (x0, y0) = poly[i];
+                let (x1, y1) = poly[(i + 1) % 4];
+                x0 * y1 - x1 * y0
+            })
+            .sum();
+
+    sum.abs() / 2.0
+}
@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Mutex, MutexGuard};
+
+use serde::de::{self, Deserializer, IgnoredAny, SeqAccess, Visitor};
+use serde::{Deserialize, Serialize, Serializer};
+
+pub const TYPE_BOOL: u32 = 0;
+pub const TYPE_DOUBLE: u32 = 1;
+pub const TYPE_INT: u32 = 2;
+pub const TYPE_FLOAT: u32 = 3;
+pub const TYPE_STRING: u32 = 4;
+pub const TYPE_RAW: u32 = 5;
+pub const TYPE_BOOLLIST: u32 = 16;
+pub const TYPE_DOUBLELIST: u32 = 17;
+pub const TYPE_INTLIST: u32 = 18;
+pub const TYPE_FLOATLIST: u32 = 19;
+pub const TYPE_STRINGLIST: u32 = 20;
+
+pub fn type_id(ty: &str) -> u32 {
+    match ty {
+        "boolean" => TYPE_BOOL,
+        "double" => TYPE_DOUBLE,
+        "int" => TYPE_INT,
+        "float" => TYPE_FLOAT,
+        "string" | "json" => TYPE_STRING,
+        "boolean[]" => TYPE_BOOLLIST,
+        "double[]" => TYPE_DOUBLELIST,
+        "int[]" => TYPE_INTLIST,
+        "float[]" => TYPE_FLOATLIST,
+        "string[]" => TYPE_STRINGLIST,
+        _ => TYPE_RAW,
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum Value {
+    Bool(bool),
+    Double(f64),
+    Int(i64),
+    Float(f32),
+    String(String),
+    Raw(Raw),
+    BoolList(Vec<bool>),
+    DoubleList(Vec<f64>),
+    IntList(Vec<i64>),
+    FloatList(Vec<f32>),
+    StringList(Vec<String>),
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Raw(pub Vec<u8>);
+
+impl Serialize for Raw {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Raw {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct RawVisitor;
+
+        impl<'de> Visitor<'de> for RawVisitor {
+            type Value = Raw;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("raw bytes")
+            }
+
+            fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Raw, E> {
+                Ok(Raw(v.to_vec()))
+            }
+
+            fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Raw, E> {
+                Ok(Raw(v))
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Raw, E> {
+                Ok(Raw(v.as_bytes().to_vec()))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Raw, A::Error> {
+                let mut buf = Vec::new();
+                while let Some(b) = seq.next_element()? {
+                    buf.push(b);
+                }
+                Ok(Raw(buf))
+            }
+        }
+
+        deserializer.deserialize_any(RawVisitor)
+    }
+}
+
+pub struct Update {
+    pub id: i64,
+    pub time: i64,
+    pub value: Option<Value>,
+}
+
+impl<'de> Deserialize<'de> for Update {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct UpdateVisitor;
+
+        impl<'de> Visitor<'de> for UpdateVisitor {
+            type Value = Update;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("nt4 value update")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Update, A::Error> {
+                let id = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(0, &self))?;
+                let time = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(1, &self))?;
+                let ty = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(2, &self))?;
+
+                let value = match ty {
+                    TYPE_BOOL => seq.next_element()?.map(Value::Bool),
+                    TYPE_DOUBLE => seq.next_element()?.map(Value::Double),
+                    TYPE_INT => seq.next_element()?.map(Value::Int),
+                    TYPE_FLOAT => seq.next_element()?.map(Value::Float),
+                    TYPE_STRING => seq.next_element()?.map(Value::String),
+                    TYPE_BOOLLIST => seq.next_element()?.map(Value::BoolList),
+                    TYPE_DOUBLELIST => seq.next_element()?.map(Value::DoubleList),
+                    TYPE_INTLIST => seq.next_element()?.map(Value::IntList),
+                    TYPE_FLOATLIST => seq.next_element()?.map(Value::FloatList),
+                    TYPE_STRINGLIST => seq.next_element()?.map(Value::StringList),
+                    TYPE_RAW => seq.next_element()?.map(Value::Raw),
+                    _ => seq.next_element::<IgnoredAny>()?.and(None),
+                };
+
+                Ok(Update { id, time, value })
+            }
+        }
+
+        deserializer.deserialize_seq(UpdateVisitor)
+    }
+}
+
+pub fn parse_updates(buf: &[u8]) -> Result<Vec<Update>, rmp_serde::decode::Error> {
+    let mut de = rmp_serde::Deserializer::new(buf);
+    let mut updates = Vec::new();
+
+    while !de.get_ref().is_empty() {
+        updates.push(Update::deserialize(&mut de)?);
+    }
+
+    Ok(updates)
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Topic {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: String,
+    pub value: Option<Value>,
+    pub time: i64,
+}
+
+#[derive(Default)]
+pub struct Topics {
+    ids: Mutex<HashMap<i64, String>>,
+    topics: Mutex<HashMap<String, Topic>>,
+}
+
+impl Topics {
+    pub fn announce(&self, id: i64, name: &str, ty: &str) {
+        self.ids().insert(id, name.to_string());
+
+        self.topics()
+            .entry(name.to_string())
+            .and_modify(|t| t.ty = ty.to_string())
+            .or_insert_with(|| Topic {
+                name: name.to_string(),
+                ty: ty.to_string(),
+                value: None,
+                time: 0,
+            });
+    }
+
+    pub fn unannounce(&self, id: i64) {
+        if let Some(name) = self.ids().remove(&id) {
+            self.topics().remove(&name);
+        }
+    }
+
+    pub fn update(&self, update: Update) {
+        let Some(name) = self.ids().get(&update.id).cloned() else { return };
+
+        if let Some(topic) = self.topics().get_mut(&name) {
+            topic.value = update.value;
+            topic.time = update.time;
+        }
+    }
+
+    pub fn clear(&self) {
+        self.ids().clear();
+        self.topics().clear();
+    }
+
+    pub fn get(&self, name: &str) -> Option<Topic> {
+        self.topics().get(name).cloned()
+    }
+
+    pub fn all(&self) -> Vec<Topic> {
+        let mut topics: Vec<Topic> = self.topics().values().cloned().collect();
+        topics.sort_by(|a, b| a.name.cmp(&b.name));
+        topics
+    }
+
+    fn ids(&self) -> MutexGuard<'_, HashMap<i64, String>> {
+        self.ids.lock().unwrap()
+    }
+
+    fn topics(&self) -> MutexGuard<'_, HashMap<String, Topic>> {
+        self.topics.lock().unwrap()
+    }
+}
@@ -0,0 +1,345 @@
+use crate::calib::Calibration;
+use crate::config::{self, Config, Filter, RecordConfig};
+use crate::data::CameraTag;
+
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Result};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+
+pub const MAGIC: &[u8; 8] = b"DLOG\0\0\0\x01";
+
+/// Frames held for the writer before new ones are dropped.
+const QUEUE: usize = 64;
+
+/// One length-prefixed msgpack entry in a segment. Every segment starts with
+/// the camera's config, and a new one follows whenever it changes. `filter`
+/// is the global filter from the settings, applied on top of the camera's.
+#[derive(Serialize, Deserialize)]
+pub enum Entry {
+    Config {
+        camera: u32,
+        config: Box<Config>,
+        #[serde(default)]
+        filter: Filter,
+        calibration: Option<Calibration>,
+    },
+    Frame(Record),
+}
+
+/// A captured frame as written to disk, with `frame` holding the PNG
+/// compressed luma image and `ms` the time spent in `Detector::process`.
+#[derive(Serialize, Deserialize)]
+pub struct Record {
+    pub camera: u32,
+    pub seq: u64,
+    pub time: f64,
+    pub width: u32,
+    pub height: u32,
+    #[serde(default)]
+    pub ms: f32,
+    pub frame: Vec<u8>,
+    pub tags: Vec<CameraTag>,
+    pub rejected: Vec<CameraTag>,
+}
+
+#[derive(Clone, Default, Serialize)]
+pub struct Status {
+    pub active: bool,
+    pub session: Option<PathBuf>,
+    pub frames: u64,
+    pub dropped: u64,
+    pub bytes: u64,
+    pub segments: usize,
+}
+
+struct Message {
+    config: Config,
+    filter: Filter,
+    calibration: Option<Calibration>,
+    luma: Vec<u8>,
+    record: Record,
+}
+
+pub struct Recorder {
+    config: RecordConfig,
+    tx: Mutex<Option<SyncSender<Message>>>,
+    status: Arc<Mutex<Status>>,
+}
+
+impl Recorder {
+    pub fn new(config: RecordConfig) -> Self {
+        let recorder = Self { config, tx: None.into(), status: Default::default() };
+
+        if recorder.config.enabled {
+            if let Err(err) = recorder.start() {
+                println!("\rrecord: {} [reason: {}]", "start failed".red(), err);
+            }
+        }
+
+        recorder
+    }
+
+    /// Starts a new session, or returns the running one.
+    pub fn start(&self) -> Result<PathBuf> {
+        let mut tx = self.tx.lock().unwrap();
+
+        if let (Some(_), Some(dir)) = (tx.as_ref(), self.status().session.clone()) {
+            return Ok(dir);
+        }
+
+        let millis = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+        let dir = config::path(&self.config.dir).join(millis.to_string());
+        fs::create_dir_all(&dir)?;
+
+        let (sender, rx) = mpsc::sync_channel(QUEUE);
+        *tx = Some(sender);
+
+        *self.status() = Status { active: true, session: Some(dir.clone()), ..Default::default() };
+
+        let writer = Writer {
+            dir: dir.clone(),
+            segment_size: self.config.segment_size * 1_000_000,
+            max_size: self.config.max_size * 1_000_000,
+            segments: HashMap::new(),
+            closed: VecDeque::new(),
+            configs: HashMap::new(),
+            counts: HashMap::new(),
+            status: self.status.clone(),
+        };
+        thread::spawn(move || writer.run(rx));
+
+        println!("\rrecord: {} [path: {}]", "started".green(), dir.display());
+        Ok(dir)
+    }
+
+    /// Ends the session once the writer has drained its queue.
+    pub fn stop(&self) {
+        if self.tx.lock().unwrap().take().is_some() {
+            self.status().active = false;
+            println!("\rrecord: {}", "stopped".yellow());
+        }
+    }
+
+    pub fn active(&self) -> bool {
+        self.tx.lock().unwrap().is_some()
+    }
+
+    pub fn status(&self) -> MutexGuard<'_, Status> {
+        self.status.lock().unwrap()
+    }
+
+    /// Queues a frame without blocking, dropping it if the writer is behind.
+    pub fn record(&self, config: &Config, filter: &Filter, calibration: Option<Calibration>, luma: Vec<u8>, record: Record) {
+        let tx = self.tx.lock().unwrap();
+        let Some(tx) = tx.as_ref() else { return };
+
+        let msg = Message { config: config.clone(), filter: filter.clone(), calibration, luma, record };
+
+        if let Err(TrySendError::Full(_)) = tx.try_send(msg) {
+            self.status().dropped += 1;
+        }
+    }
+}
+
+struct Segment {
+    file: BufWriter<File>,
+    path: PathBuf,
+    size: u64,
+}
+
+struct Writer {
+    dir: PathBuf,
+    segment_size: u64,
+    max_size: u64,
+    segments: HashMap<u32, Segment>,
+    closed: VecDeque<(PathBuf, u64)>,
+    configs: HashMap<u32, String>,
+    counts: HashMap<u32, u32>,
+    status: Arc<Mutex<Status>>,
+}
+
+impl Writer {
+    fn run(mut self, rx: Receiver<Message>) {
+        for msg in rx {
+            if let Err(err) = self.write(msg) {
+                println!("\rrecord: {} [reason: {}]", "write failed".red(), err);
+            }
+        }
+
+        for (_, mut segment) in self.segments.drain() {
+            let _ = segment.file.flush();
+        }
+    }
+
+    fn write(&mut self, msg: Message) -> Result<()> {
+        let Message { config, filter, calibration, luma, mut record } = msg;
+        let camera = record.camera;
+
+        record.frame = compress(record.width, record.height, &luma)?;
+
+        if self.segments.get(&camera).is_none_or(|s| s.size >= self.segment_size) {
+            self.rotate(camera)?;
+        }
+
+        let json = serde_json::to_string(&(&config, &filter, &calibration))?;
+        if self.configs.get(&camera) != Some(&json) {
+            self.append(camera, &Entry::Config { camera, config: Box::new(config), filter, calibration })?;
+            self.configs.insert(camera, json);
+        }
+
+        self.append(camera, &Entry::Frame(record))?;
+        self.status.lock().unwrap().frames += 1;
+
+        self.trim();
+        Ok(())
+    }
+
+    fn rotate(&mut self, camera: u32) -> Result<()> {
+        if let Some(mut old) = self.segments.remove(&camera) {
+            old.file.flush()?;
+            self.closed.push_back((old.path, old.size));
+        }
+
+        let count = self.counts.entry(camera).or_default();
+        let path = self.dir.join(format!("cam{}-{:04}.dlog", camera, count));
+        *count += 1;
+
+        let mut file = BufWriter::new(File::create(&path)?);
+        file.write_all(MAGIC)?;
+
+        self.segments.insert(camera, Segment { file, path, size: MAGIC.len() as u64 });
+        self.configs.remove(&camera);
+
+        let mut status = self.status.lock().unwrap();
+        status.segments += 1;
+        status.bytes += MAGIC.len() as u64;
+
+        Ok(())
+    }
+
+    fn append(&mut self, camera: u32, entry: &Entry) -> Result<()> {
+        let buf = rmp_serde::to_vec_named(entry)?;
+        let segment = self.segments.get_mut(&camera).unwrap();
+
+        segment.file.write_all(&(buf.len() as u32).to_le_bytes())?;
+        segment.file.write_all(&buf)?;
+        segment.size += 4 + buf.len() as u64;
+
+        self.status.lock().unwrap().bytes += 4 + buf.len() as u64;
+        Ok(())
+    }
+
+    /// Deletes the oldest finished segments until the session fits its cap.
+    fn trim(&mut self) {
+        let open: u64 = self.segments.values().map(|s| s.size).sum();
+        let mut total = open + self.closed.iter().map(|(_, size)| size).sum::<u64>();
+
+        while total > self.max_size {
+            let Some((path, size)) = self.closed.pop_front() else { break };
+
+            if let Err(err) = fs::remove_file(&path) {
+                println!("\rrecord: {} [reason: {}]", "delete failed".red(), err);
+            }
+            total -= size;
+
+            let mut status = self.status.lock().unwrap();
+            status.bytes = status.bytes.saturating_sub(size);
+            status.segments -= 1;
+        }
+    }
+}
+
+fn compress(w: u32, h: u32, luma: &[u8]) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+
+    let mut encoder = png::Encoder::new(&mut buf, w, h);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_compression(png::Compression::Fast);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(luma)?;
+    writer.finish()?;
+
+    Ok(buf)
+}
+
+/// Reads the entries of one segment in order. A segment cut off mid-entry,
+/// as happens when the server is killed while recording, just ends early.
+pub struct Reader {
+    file: BufReader<File>,
+}
+
+impl Reader {
+    pub fn open(path: &Path) -> Result<Self> {
+        let mut file = BufReader::new(File::open(path)?);
+
+        let mut magic = [0; 8];
+        file.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            bail!("{}: not a recording segment", path.display());
+        }
+
+        Ok(Self { file })
+    }
+
+    fn next_entry(&mut self) -> Result<Option<Entry>> {
+        let mut len = [0; 4];
+        let mut buf = Vec::new();
+
+        let read =
+            self.file.read_exact(&mut len).and_then(|_| {
+                buf.resize(u32::from_le_bytes(len) as usize, 0);
+                self.file.read_exact(&mut buf)
+            });
+
+        match read {
+            Ok(()) => Ok(Some(rmp_serde::from_slice(&buf)?)),
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+impl Iterator for Reader {
+    type Item = Result<Entry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_entry().transpose()
+    }
+}
+
+/// Lists a session's segments, or returns `path` itself if it's a segment.
+pub fn segments(path: &Path) -> Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let mut segments: Vec<PathBuf> =
+        fs::read_dir(path)?
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|e| e == "dlog"))
+            .collect();
+
+    segments.sort();
+    Ok(segments)
+}
+
+pub fn decompress(record: &Record) -> Result<Vec<u8>> {
+    let decoder = png::Decoder::new(io::Cursor::new(&record.frame));
+    let mut reader = decoder.read_info()?;
+
+    let mut luma = vec![0; (record.width * record.height) as usize];
+    reader.next_frame(&mut luma)?;
+
+    Ok(luma)
+}
@@ -0,0 +1,304 @@
+use crate::geom::{Intrinsics, Rotation, Transform, Translation};
+
+use serde::{Deserialize, Serialize};
+
+const MAX_ITERS: usize = 50;
+const BEHIND_PENALTY: f64 = 1e4;
+
+const DISTINCT_COS: f64 = 0.9998;
+
+#[derive(Clone, Copy)]
+pub struct Observation {
+    pub id: u32,
+    pub robot_to_camera: Transform,
+    pub intr: Intrinsics,
+    pub field_to_tag: Transform,
+    pub size: f64,
+    pub corners: [(f64, f64); 4],
+}
+
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+pub struct TagPoses {
+    pub best: Transform,
+    pub alt: Transform,
+    pub best_error: f64,
+    pub alt_error: f64,
+    pub ambiguity: f64,
+}
+
+#[derive(Clone, Serialize)]
+pub struct Solution {
+    pub pose: Transform,
+    pub error: f64,
+    pub ids: Vec<u32>,
+}
+
+/// Tag corners in the tag frame, ordered to match `Tag::corners`.
+pub fn tag_corners(size: f64) -> [Translation; 4] {
+    let s = size / 2.0;
+
+    [
+        Translation::new(0.0, -s, s),
+        Translation::new(0.0, s, s),
+        Translation::new(0.0, -s, -s),
+        Translation::new(0.0, s, -s),
+    ]
+}
+
+/// Fits the field-to-robot pose to every observed tag corner, starting from `guess`.
+pub fn solve_pose(obs: &[Observation], guess: Transform) -> Option<Solution> {
+    if obs.is_empty() {
+        return None;
+    }
+
+    let residuals = |x: &[f64]| -> Vec<f64> {
+        let pose = perturb(guess, x);
+        obs.iter().flat_map(|o| reproject(o, pose)).collect()
+    };
+
+    let (x, cost) = lm(vec![0.0; 6], residuals)?;
+
+    let mut ids: Vec<u32> = obs.iter().map(|o| o.id).collect();
+    ids.sort();
+    ids.dedup();
+
+    Some(Solution {
+        pose: perturb(guess, &x),
+        error: (cost / (obs.len() * 4) as f64).sqrt(),
+        ids,
+    })
+}
+
+/// Refines a single tag's camera-to-tag pose from its corners and finds the
+/// mirrored alternate solution.
+pub fn tag_poses(intr: Intrinsics, size: f64, corners: [(f64, f64); 4], initial: Transform) -> Option<TagPoses> {
+    let (best, best_error) = refine_tag(intr, size, corners, initial)?;
+
+    let normal = best.rotation.rotate(Translation::new(1.0, 0.0, 0.0));
+    let sight = best.translation.scale(1.0 / best.translation.norm());
+    let mirrored = sight.scale(2.0 * normal.dot(sight)) - normal;
+
+    let axis = normal.cross(mirrored);
+    let angle = axis.norm().atan2(normal.dot(mirrored));
+
+    let flip = Rotation::from_rotvec(axis.scale(angle / axis.norm().max(1e-12)));
+    let guess = Transform::new(best.translation, (flip * best.rotation).normalize());
+
+    let (alt, alt_error) =
+        refine_tag(intr, size, corners, guess)
+            .filter(|(alt, _)| {
+                let n = alt.rotation.rotate(Translation::new(1.0, 0.0, 0.0));
+                n.dot(normal) < DISTINCT_COS
+            })
+            .unwrap_or((best, f64::INFINITY));
+
+    let (best, alt, best_error, alt_error) =
+        if alt_error < best_error {
+            (alt, best, alt_error, best_error)
+        } else {
+            (best, alt, best_error, alt_error)
+        };
+
+    Some(TagPoses {
+        best,
+        alt,
+        best_error,
+        alt_error,
+        ambiguity: if alt_error > 0.0 { best_error / alt_error } else { 1.0 },
+    })
+}
+
+fn refine_tag(intr: Intrinsics, size: f64, corners: [(f64, f64); 4], guess: Transform) -> Option<(Transform, f64)> {
+    let obs = [Observation {
+        id: 0,
+        robot_to_camera: Transform::default(),
+        intr,
+        field_to_tag: Transform::default(),
+        size,
+        corners,
+    }];
+
+    let sol = solve_pose(&obs, guess.inverse())?;
+    Some((sol.pose.inverse(), sol.error))
+}
+
+/// Applies a translation and rotation vector offset to a pose.
+pub fn perturb(pose: Transform, x: &[f64]) -> Transform {
+    Transform::new(
+        pose.translation + Translation::new(x[0], x[1], x[2]),
+        (Rotation::from_rotvec(Translation::new(x[3], x[4], x[5])) * pose.rotation).normalize(),
+    )
+}
+
+fn reproject(o: &Observation, field_to_robot: Transform) -> [f64; 8] {
+    let camera_to_tag = (field_to_robot * o.robot_to_camera).inverse() * o.field_to_tag;
+    let mut res = [BEHIND_PENALTY; 8];
+
+    for (i, corner) in tag_corners(o.size).into_iter().enumerate() {
+        if let Some((u, v)) = o.intr.project(camera_to_tag.apply(corner)) {
+            res[i * 2] = u - o.corners[i].0;
+            res[i * 2 + 1] = v - o.corners[i].1;
+        }
+    }
+
+    res
+}
+
+/// Levenberg-Marquardt with a forward-difference Jacobian. Returns the
+/// parameters and the final sum of squared residuals.
+pub fn lm<F: Fn(&[f64]) -> Vec<f64>>(mut x: Vec<f64>, f: F) -> Option<(Vec<f64>, f64)> {
+    let n = x.len();
+
+    let mut r = f(&x);
+    let mut cost = sum_sq(&r);
+    let mut lambda = 1e-3;
+
+    for _ in 0..MAX_ITERS {
+        let jac: Vec<Vec<f64>> =
+            (0..n)
+                .map(|j| {
+                    let eps = 1e-6 * x[j].abs().max(1.0);
+
+                    let mut xp = x.clone();
+                    xp[j] += eps;
+
+                    f(&xp).iter().zip(&r).map(|(a, b)| (a - b) / eps).collect()
+                })
+                .collect();
+
+        let mut jtj = vec![vec![0.0; n]; n];
+        let mut jtr = vec![0.0; n];
+
+        for a in 0..n {
+            for b in a..n {
+                let v: f64 = jac[a].iter().zip(&jac[b]).map(|(p, q)| p * q).sum();
+                jtj[a][b] = v;
+                jtj[b][a] = v;
+            }
+            jtr[a] = jac[a].iter().zip(&r).map(|(p, q)| p * q).sum();
+        }
+
+        let mut improved = false;
+
+        while lambda < 1e10 {
+            let mut a = jtj.clone();
+            for (i, row) in a.iter_mut().enumerate() {
+                row[i] += lambda * jtj[i][i].max(1e-9);
+            }
+
+            let Some(step) = solve_linear(a, jtr.iter().map(|v| -v).collect()) else {
+                lambda *= 10.0;
+                continue;
+            };
+
+            let xn: Vec<f64> = x.iter().zip(&step).map(|(a, b)| a + b).collect();
+            let rn = f(&xn);
+            let cn = sum_sq(&rn);
+
+            if cn < cost {
+                let done = (cost - cn) < 1e-12 * cost.max(1.0) || sum_sq(&step) < 1e-20;
+
+                x = xn;
+                r = rn;
+                cost = cn;
+                lambda = (lambda / 10.0).max(1e-12);
+                improved = !done;
+
+                break;
+            }
+
+            lambda *= 10.0;
+        }
+
+        if !improved {
+            break;
+        }
+    }
+
+    cost.is_finite().then_some((x, cost))
+}
+
+/// Solves `a * x = b` by Gaussian elimination with partial pivoting.
+pub fn solve_linear(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+    let n = b.len();
+
+    for col in 0..n {
+        let pivot = (col..n).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+        if a[pivot][col].abs() < 1e-15 {
+            return None;
+        }
+
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+
+        for row in col + 1..n {
+            let k = a[row][col] / a[col][col];
+            if k == 0.0 {
+                continue;
+            }
+
+            let (top, bottom) = a.split_at_mut(row);
+            for (v, p) in bottom[0][col..].iter_mut().zip(&top[col][col..]) {
+                *v -= k * p;
+            }
+            b[row] -= k * b[col];
+        }
+    }
+
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let sum: f64 = (row + 1..n).map(|c| a[row][c] * x[c]).sum();
+        x[row] = (b[row] - sum) / a[row][row];
+    }
+
+    Some(x)
+}
+
+/// Returns the eigenvector of a symmetric matrix with the smallest
+/// eigenvalue, using cyclic Jacobi rotations.
+pub fn smallest_eigenvector(mut a: Vec<Vec<f64>>) -> Vec<f64> {
+    let n = a.len();
+    let mut v: Vec<Vec<f64>> = (0..n).map(|i| (0..n).map(|j| if i == j { 1.0 } else { 0.0 }).collect()).collect();
+
+    for _ in 0..100 {
+        let off: f64 = (0..n).flat_map(|p| (p + 1..n).map(move |q| (p, q))).map(|(p, q)| a[p][q] * a[p][q]).sum();
+        if off < 1e-24 {
+            break;
+        }
+
+        for p in 0..n {
+            for q in p + 1..n {
+                if a[p][q].abs() < 1e-300 {
+                    continue;
+                }
+
+                let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+                let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                let c = 1.0 / (t * t + 1.0).sqrt();
+                let s = t * c;
+
+                for m in [&mut a, &mut v] {
+                    for row in m.iter_mut() {
+                        let (kp, kq) = (row[p], row[q]);
+                        row[p] = c * kp - s * kq;
+                        row[q] = s * kp + c * kq;
+                    }
+                }
+
+                let (lo, hi) = a.split_at_mut(q);
+                for (kp, kq) in lo[p].iter_mut().zip(hi[0].iter_mut()) {
+                    let (pk, qk) = (*kp, *kq);
+                    *kp = c * pk - s * qk;
+                    *kq = s * pk + c * qk;
+                }
+            }
+        }
+    }
+
+    let min = (0..n).min_by(|&i, &j| a[i][i].total_cmp(&a[j][j])).unwrap_or(0);
+    v.iter().map(|row| row[min]).collect()
+}
+
+fn sum_sq(v: &[f64]) -> f64 {
+    v.iter().map(|x| x * x).sum()
+}
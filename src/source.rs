@@ -0,0 +1,93 @@
+mod camera;
+mod images;
+mod render;
+mod sim;
+mod synthetic;
+mod tag36h11;
+mod video;
+
+pub use self::camera::CameraSource;
+pub use self::images::ImageSource;
+pub use self::sim::SimSource;
+pub use self::synthetic::SyntheticSource;
+pub use self::video::VideoSource;
+
+use crate::config::{self, Config, Source};
+use crate::geom::Transform;
+use crate::state::State;
+
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use serde::Serialize;
+
+pub struct Frame {
+    pub width: u32,
+    pub height: u32,
+    pub luma: Vec<u8>,
+    pub captured: Option<Instant>,
+    pub truth: Vec<Truth>,
+}
+
+/// Where a tag really was in a rendered frame, as a camera-to-tag transform
+/// and its projected corners.
+#[derive(Clone, Serialize)]
+pub struct Truth {
+    pub id: u32,
+    pub size: f64,
+    pub pose: Transform,
+    pub corners: Option<[(f64, f64); 4]>,
+}
+
+pub trait FrameSource {
+    /// Blocks until the next frame is ready, or returns `None` once a source
+    /// that doesn't loop has run out of frames.
+    fn frame(&mut self) -> Result<Option<Frame>>;
+
+    /// Switches resolution in place, returning `false` if the source has to
+    /// be reopened instead.
+    fn set_resolution(&mut self, _res: (u32, u32)) -> Result<bool> {
+        Ok(false)
+    }
+}
+
+pub fn open(state: &Arc<State>, config: &Config) -> Result<Box<dyn FrameSource>> {
+    let server = &config.server;
+    let fov = config.detector.fov as f64;
+
+    Ok(match &server.source {
+        Source::Camera => Box::new(CameraSource::open(server.camera, server.res)?),
+        Source::Video { path, fps, looping } => Box::new(VideoSource::open(&config::path(path), *fps, *looping)?),
+        Source::Images { path, fps, looping } => Box::new(ImageSource::open(&config::path(path), *fps, *looping)?),
+        Source::Synthetic(scene) => Box::new(SyntheticSource::open(scene, server.res, fov)?),
+        Source::Sim(sim) => Box::new(SimSource::open(state, sim, server.res, fov)?),
+    })
+}
+
+/// Releases frames at a fixed rate.
+struct Pacer {
+    period: Duration,
+    next: Option<Instant>,
+}
+
+impl Pacer {
+    fn new(fps: f64) -> Self {
+        Self { period: Duration::from_secs_f64(1.0 / fps.max(0.1)), next: None }
+    }
+
+    fn wait(&mut self) -> Instant {
+        let now = Instant::now();
+        let next = self.next.unwrap_or(now);
+
+        if next > now {
+            thread::sleep(next - now);
+        }
+
+        let released = Instant::now();
+        self.next = Some(if released > next + self.period { released } else { next } + self.period);
+
+        released
+    }
+}
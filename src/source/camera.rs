@@ -0,0 +1,71 @@
+use super::{Frame, FrameSource};
+
+use anyhow::{anyhow, Result};
+
+use nokhwa::Camera;
+use nokhwa::pixel_format::LumaFormat;
+use nokhwa::utils::{CameraIndex, RequestedFormat, RequestedFormatType, Resolution};
+
+pub struct CameraSource {
+    camera: Camera,
+}
+
+impl CameraSource {
+    pub fn open(index: u32, (w, h): (u32, u32)) -> Result<Self> {
+        let mut camera =
+            Camera::new(CameraIndex::Index(index), request(w, h))
+                .map_err(|e| anyhow!("camera {}: {}", index, e))?;
+
+        camera.open_stream().map_err(|e| anyhow!("camera {}: {}", index, e))?;
+
+        Ok(Self { camera })
+    }
+}
+
+impl FrameSource for CameraSource {
+    fn frame(&mut self) -> Result<Option<Frame>> {
+        let frame = self.camera.frame().map_err(|e| anyhow!("{}", e))?;
+
+        // nokhwa 0.10 buffers carry no capture timestamp
+        let res = frame.resolution();
+        let (w, h) = (res.width(), res.height());
+
+        let decoded = frame.decode_image::<LumaFormat>().map_err(|e| anyhow!("{}", e))?;
+        let bytes = decoded.to_vec();
+
+        let stride = bytes.len() as u32 / h;
+        let luma =
+            (0..h)
+                .flat_map(|y| {
+                    let src = (y * stride) as usize;
+                    bytes[src..src + w as usize].iter().copied()
+                })
+                .collect();
+
+        Ok(Some(Frame { width: w, height: h, luma, captured: None, truth: Vec::new() }))
+    }
+
+    fn set_resolution(&mut self, (w, h): (u32, u32)) -> Result<bool> {
+        if cfg!(target_os = "macos") {
+            return Ok(false);
+        }
+
+        self.camera.stop_stream().map_err(|e| anyhow!("{}", e))?;
+        self.camera.set_camera_requset(request(w, h)).map_err(|e| anyhow!("{}", e))?;
+        self.camera.open_stream().map_err(|e| anyhow!("{}", e))?;
+
+        Ok(true)
+    }
+}
+
+impl Drop for CameraSource {
+    fn drop(&mut self) {
+        let _ = self.camera.stop_stream();
+    }
+}
+
+fn request(w: u32, h: u32) -> RequestedFormat {
+    RequestedFormat::new::<LumaFormat>(
+        RequestedFormatType::HighestResolution(Resolution::new(w, h)),
+    )
+}
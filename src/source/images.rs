@@ -0,0 +1,150 @@
+use super::{Frame, FrameSource, Pacer};
+
+use std::fs::{self, File};
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Result};
+
+const DEFAULT_FPS: f64 = 30.0;
+
+/// Plays back a directory of PNG or binary PGM frames in filename order.
+pub struct ImageSource {
+    paths: Vec<PathBuf>,
+    next: usize,
+    looping: bool,
+    pacer: Pacer,
+}
+
+impl ImageSource {
+    pub fn open(dir: &Path, fps: Option<f64>, looping: bool) -> Result<Self> {
+        let mut paths: Vec<PathBuf> =
+            fs::read_dir(dir)
+                .map_err(|e| anyhow!("{}: {}", dir.display(), e))?
+                .filter_map(|e| e.ok().map(|e| e.path()))
+                .filter(|p| matches!(extension(p).as_deref(), Some("png" | "pgm")))
+                .collect();
+
+        if paths.is_empty() {
+            bail!("{}: no png or pgm frames", dir.display());
+        }
+        paths.sort();
+
+        Ok(Self { paths, next: 0, looping, pacer: Pacer::new(fps.unwrap_or(DEFAULT_FPS)) })
+    }
+}
+
+impl FrameSource for ImageSource {
+    fn frame(&mut self) -> Result<Option<Frame>> {
+        if self.next == self.paths.len() {
+            if !self.looping {
+                return Ok(None);
+            }
+            self.next = 0;
+        }
+
+        let path = &self.paths[self.next];
+        self.next += 1;
+
+        let (width, height, luma) =
+            match extension(path).as_deref() {
+                Some("png") => read_png(path),
+                _ => read_pgm(path),
+            }
+            .map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+
+        let captured = self.pacer.wait();
+
+        Ok(Some(Frame { width, height, luma, captured: Some(captured), truth: Vec::new() }))
+    }
+}
+
+pub(super) fn extension(path: &Path) -> Option<String> {
+    path.extension().map(|e| e.to_string_lossy().to_lowercase())
+}
+
+pub(super) fn read_png(path: &Path) -> Result<(u32, u32, Vec<u8>)> {
+    let mut decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+
+    let mut reader = decoder.read_info()?;
+    let mut buf = vec![0; reader.output_buffer_size().ok_or_else(|| anyhow!("image too large"))?];
+    let info = reader.next_frame(&mut buf)?;
+
+    let channels = info.color_type.samples();
+    let (w, h) = (info.width, info.height);
+
+    let luma =
+        (0..h as usize)
+            .flat_map(|y| {
+                let row = &buf[y * info.line_size..];
+                (0..w as usize).map(move |x| {
+                    let px = &row[x * channels..];
+                    match channels {
+                        1 | 2 => px[0],
+                        _ => ((px[0] as u32 * 77 + px[1] as u32 * 150 + px[2] as u32 * 29) >> 8) as u8,
+                    }
+                })
+            })
+            .collect();
+
+    Ok((w, h, luma))
+}
+
+pub(super) fn read_pgm(path: &Path) -> Result<(u32, u32, Vec<u8>)> {
+    let mut bytes = Vec::new();
+    File::open(path)?.read_to_end(&mut bytes)?;
+
+    let mut pos = 0;
+    let mut token = || -> Result<String> {
+        loop {
+            while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+                pos += 1;
+            }
+            if pos < bytes.len() && bytes[pos] == b'#' {
+                while pos < bytes.len() && bytes[pos] != b'\n' {
+                    pos += 1;
+                }
+                continue;
+            }
+            break;
+        }
+
+        let start = pos;
+        while pos < bytes.len() && !bytes[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+
+        Ok(String::from_utf8_lossy(&bytes[start..pos]).into_owned())
+    };
+
+    if token()? != "P5" {
+        bail!("only binary (P5) pgm is supported");
+    }
+
+    let w: u32 = token()?.parse()?;
+    let h: u32 = token()?.parse()?;
+    let max: u32 = token()?.parse()?;
+
+    if max == 0 || max > u16::MAX as u32 {
+        bail!("invalid maxval {}", max);
+    }
+
+    let data = bytes.get(pos + 1..).ok_or_else(|| anyhow!("truncated header"))?;
+    let n = w.checked_mul(h).ok_or_else(|| anyhow!("image too large"))? as usize;
+
+    // samples run from 0 to maxval, so stretch them to the full byte range
+    let scale = |v: u32| (v.min(max) * 255 / max) as u8;
+
+    let luma =
+        if max < 256 {
+            let bytes = data.get(..n).ok_or_else(|| anyhow!("truncated image"))?;
+            bytes.iter().map(|&v| scale(v as u32)).collect()
+        } else {
+            let len = n.checked_mul(2).ok_or_else(|| anyhow!("image too large"))?;
+            let wide = data.get(..len).ok_or_else(|| anyhow!("truncated image"))?;
+            wide.chunks(2).map(|c| scale(u16::from_be_bytes([c[0], c[1]]) as u32)).collect()
+        };
+
+    Ok((w, h, luma))
+}
@@ -0,0 +1,270 @@
+use super::{images, tag36h11, Truth};
+
+use crate::calib;
+use crate::config::{self, Render};
+use crate::geom::{Intrinsics, Transform, Translation};
+use crate::solve;
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Result};
+
+/// White margin drawn around each tag, as a fraction of its size.
+const QUIET: f64 = 0.25;
+const SAMPLES: usize = 2;
+
+struct Image {
+    width: usize,
+    height: usize,
+    luma: Vec<u8>,
+}
+
+/// Draws tags at camera-to-tag poses through a pinhole model.
+pub struct Renderer {
+    render: Render,
+    intr: Intrinsics,
+    width: u32,
+    height: u32,
+    images: HashMap<u32, Image>,
+    rng: u64,
+}
+
+impl Renderer {
+    pub fn new(render: &Render, (width, height): (u32, u32), fov: f64) -> Result<Self> {
+        let mut images: HashMap<u32, Image> =
+            tag36h11::ids()
+                .filter_map(|id| {
+                    let luma = tag36h11::pattern(id)?;
+                    Some((id, Image { width: tag36h11::SIZE, height: tag36h11::SIZE, luma }))
+                })
+                .collect();
+
+        if let Some(dir) = &render.images {
+            images.extend(load_images(&config::path(dir), render.crop)?);
+        }
+
+        Ok(Self {
+            render: render.clone(),
+            intr: Intrinsics::from_fov(width, height, fov),
+            width,
+            height,
+            images,
+            rng: 0x9e37_79b9_7f4a_7c15,
+        })
+    }
+
+    /// Returns a tag id that has no image.
+    pub fn missing(&self, mut ids: impl Iterator<Item = u32>) -> Option<u32> {
+        ids.find(|id| !self.images.contains_key(id))
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Renders tags given as id, camera-to-tag pose and size, returning the
+    /// luma image and where each tag landed.
+    pub fn render(&mut self, tags: &[(u32, Transform, f64)]) -> (Vec<u8>, Vec<Truth>) {
+        let mut tags = tags.to_vec();
+
+        // far tags first so nearer ones paint over them
+        tags.sort_by(|a, b| b.1.translation.norm().total_cmp(&a.1.translation.norm()));
+
+        let (w, h) = (self.width as usize, self.height as usize);
+        let mut img = vec![self.render.background as f32; w * h];
+
+        for &(id, pose, size) in &tags {
+            let facing = pose.rotation.rotate(Translation::new(1.0, 0.0, 0.0));
+            let inverse = pose.inverse();
+
+            let Some((x0, y0, x1, y1)) = self.bounds(pose, size * (1.0 + 2.0 * QUIET)) else {
+                continue;
+            };
+
+            let image = self.images.get(&id);
+
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    let mut sum = 0.0;
+
+                    for s in 0..SAMPLES * SAMPLES {
+                        let u = x as f64 + ((s % SAMPLES) as f64 + 0.5) / SAMPLES as f64;
+                        let v = y as f64 + ((s / SAMPLES) as f64 + 0.5) / SAMPLES as f64;
+
+                        let ray = Translation::new(1.0, (self.intr.cx - u) / self.intr.fx, (self.intr.cy - v) / self.intr.fy);
+
+                        let denom = facing.dot(ray);
+                        let t = facing.dot(pose.translation) / denom;
+
+                        let value =
+                            if denom < 0.0 && t > 0.0 {
+                                let local = inverse.apply(ray.scale(t));
+                                sample(image, local.y / size + 0.5, 0.5 - local.z / size)
+                            } else {
+                                None
+                            };
+
+                        sum += value.unwrap_or(img[y * w + x]);
+                    }
+
+                    img[y * w + x] = sum / (SAMPLES * SAMPLES) as f32;
+                }
+            }
+        }
+
+        let mut out: Vec<f64> =
+            if self.render.blur > 0.0 {
+                calib::blur(w, h, &img, self.render.blur)
+            } else {
+                img.iter().map(|&v| v as f64).collect()
+            };
+
+        if self.render.noise > 0.0 {
+            for v in &mut out {
+                *v += self.gaussian() * self.render.noise;
+            }
+        }
+
+        let luma = out.iter().map(|v| (v.clamp(0.0, 1.0) * 255.0).round() as u8).collect();
+
+        let truth =
+            tags
+                .iter()
+                .map(|&(id, pose, size)| {
+                    let corners = solve::tag_corners(size).map(|c| self.intr.project(pose.apply(c)));
+
+                    Truth {
+                        id,
+                        size,
+                        pose,
+                        corners: corners.iter().all(Option::is_some).then(|| corners.map(Option::unwrap_or_default)),
+                    }
+                })
+                .collect();
+
+        (luma, truth)
+    }
+
+    /// Pixel bounds of a square of side `size` at `pose`, or `None` if it's
+    /// entirely off screen, behind the camera or facing away from it.
+    fn bounds(&self, pose: Transform, size: f64) -> Option<(usize, usize, usize, usize)> {
+        let (w, h) = (self.width as f64, self.height as f64);
+
+        let facing = pose.rotation.rotate(Translation::new(1.0, 0.0, 0.0));
+        if facing.dot(pose.translation) >= 0.0 {
+            return None;
+        }
+
+        let projected: Vec<Option<(f64, f64)>> =
+            solve::tag_corners(size)
+                .iter()
+                .map(|&c| self.intr.project(pose.apply(c)))
+                .collect();
+
+        if projected.iter().all(Option::is_none) {
+            return None;
+        }
+
+        // a square straddling the camera plane has no useful bound, so scan everything
+        let Some(pts) = projected.into_iter().collect::<Option<Vec<_>>>() else {
+            return Some((0, 0, self.width as usize, self.height as usize));
+        };
+
+        let (mut x0, mut y0, mut x1, mut y1) = (f64::MAX, f64::MAX, f64::MIN, f64::MIN);
+        for (u, v) in pts {
+            (x0, y0, x1, y1) = (x0.min(u), y0.min(v), x1.max(u), y1.max(v));
+        }
+
+        if x1 < 0.0 || y1 < 0.0 || x0 >= w || y0 >= h {
+            return None;
+        }
+
+        Some((
+            x0.max(0.0) as usize,
+            y0.max(0.0) as usize,
+            (x1.ceil() + 1.0).min(w) as usize,
+            (y1.ceil() + 1.0).min(h) as usize,
+        ))
+    }
+
+    fn gaussian(&mut self) -> f64 {
+        let u1 = self.uniform().max(f64::MIN_POSITIVE);
+        let u2 = self.uniform();
+
+        (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+    }
+
+    fn uniform(&mut self) -> f64 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+
+        (self.rng >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Samples a tag at normalized coordinates across its black border, with
+/// the quiet zone around it drawn white. Tags without an image, such as ones
+/// added to the field layout after the source opened, are solid.
+fn sample(image: Option<&Image>, x: f64, y: f64) -> Option<f32> {
+    if x < -QUIET || y < -QUIET || x >= 1.0 + QUIET || y >= 1.0 + QUIET {
+        return None;
+    }
+    if !(0.0..1.0).contains(&x) || !(0.0..1.0).contains(&y) {
+        return Some(1.0);
+    }
+
+    let Some(image) = image else {
+        return Some(0.0);
+    };
+
+    let px = (x * image.width as f64) as usize;
+    let py = (y * image.height as f64) as usize;
+
+    Some(image.luma[py * image.width + px] as f32 / 255.0)
+}
+
+/// Loads tag images keyed by the number at the end of their file name, so
+/// both `5.png` and `tag36_11_00005.png` map to tag 5. `crop` trims the
+/// white border the published tag images carry.
+fn load_images(dir: &Path, crop: u32) -> Result<HashMap<u32, Image>> {
+    let mut images = HashMap::new();
+
+    for entry in fs::read_dir(dir).map_err(|e| anyhow!("{}: {}", dir.display(), e))? {
+        let path = entry?.path();
+
+        let Some(stem) = path.file_stem().map(|s| s.to_string_lossy().into_owned()) else {
+            continue;
+        };
+        let digits = stem.len() - stem.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+        let Ok(id) = stem[stem.len() - digits..].parse() else {
+            continue;
+        };
+
+        let (w, h, luma) =
+            match images::extension(&path).as_deref() {
+                Some("png") => images::read_png(&path)?,
+                Some("pgm") => images::read_pgm(&path)?,
+                _ => continue,
+            };
+
+        if w <= 2 * crop || h <= 2 * crop {
+            bail!("{}: image too small to crop", path.display());
+        }
+
+        let (width, height) = ((w - 2 * crop) as usize, (h - 2 * crop) as usize);
+        let luma =
+            (0..height)
+                .flat_map(|y| {
+                    let src = (y + crop as usize) * w as usize + crop as usize;
+                    luma[src..src + width].iter().copied()
+                })
+                .collect();
+
+        images.insert(id, Image { width, height, luma });
+    }
+
+    Ok(images)
+}
@@ -0,0 +1,65 @@
+use super::{Frame, FrameSource, Pacer};
+use super::render::Renderer;
+
+use crate::config::Sim;
+use crate::nt;
+use crate::state::State;
+
+use std::sync::Arc;
+
+use anyhow::{bail, Result};
+
+pub struct SimSource {
+    state: Arc<State>,
+    topic: String,
+    renderer: Renderer,
+    pacer: Pacer,
+}
+
+impl SimSource {
+    pub fn open(state: &Arc<State>, sim: &Sim, res: (u32, u32), fov: f64) -> Result<Self> {
+        let renderer = Renderer::new(&sim.render, res, fov)?;
+
+        if let Some(id) = renderer.missing(state.field().tags.iter().map(|t| t.id)) {
+            bail!("no built-in pattern or image for tag {}", id);
+        }
+
+        Ok(Self {
+            state: state.clone(),
+            topic: sim.topic.clone(),
+            renderer,
+            pacer: Pacer::new(sim.fps),
+        })
+    }
+}
+
+impl FrameSource for SimSource {
+    fn frame(&mut self) -> Result<Option<Frame>> {
+        let captured = self.pacer.wait();
+
+        let robot =
+            self.state.topics
+                .get(&self.topic)
+                .and_then(|t| t.value.as_ref().and_then(|v| nt::unpack_pose(&t.ty, v)));
+
+        // until the robot pose shows up the camera just sees an empty field
+        let tags: Vec<_> = match robot {
+            Some(robot) => {
+                let config = self.state.config().clone();
+                let camera = (robot * config.mount.transform()).inverse();
+
+                let field = self.state.field();
+                field.tags
+                    .iter()
+                    .filter_map(|t| Some((t.id, camera * field.pose(t.id)?, config.tag_size(Some(t.id)))))
+                    .collect()
+            }
+            None => Vec::new(),
+        };
+
+        let (luma, truth) = self.renderer.render(&tags);
+        let (width, height) = self.renderer.size();
+
+        Ok(Some(Frame { width, height, luma, captured: Some(captured), truth }))
+    }
+}
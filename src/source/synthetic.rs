@@ -0,0 +1,83 @@
+use super::{Frame, FrameSource, Pacer};
+use super::render::Renderer;
+
+use crate::config::{Keyframe, Mount, Scene, SceneTag};
+use crate::field::TAG_SIZE;
+
+use anyhow::{bail, Result};
+
+pub struct SyntheticSource {
+    scene: Scene,
+    renderer: Renderer,
+    index: u64,
+    pacer: Pacer,
+}
+
+impl SyntheticSource {
+    pub fn open(scene: &Scene, res: (u32, u32), fov: f64) -> Result<Self> {
+        let renderer = Renderer::new(&scene.render, res, fov)?;
+
+        if let Some(id) = renderer.missing(scene.tags.iter().map(|t| t.id)) {
+            bail!("no built-in pattern or image for tag {}", id);
+        }
+
+        Ok(Self { scene: scene.clone(), renderer, index: 0, pacer: Pacer::new(scene.fps) })
+    }
+}
+
+impl FrameSource for SyntheticSource {
+    fn frame(&mut self) -> Result<Option<Frame>> {
+        let time = self.index as f64 / self.scene.fps.max(0.1);
+        self.index += 1;
+
+        let tags: Vec<_> =
+            self.scene.tags
+                .iter()
+                .map(|t| (t.id, pose_at(t, time).transform(), t.size.unwrap_or(TAG_SIZE)))
+                .collect();
+
+        let (luma, truth) = self.renderer.render(&tags);
+        let (width, height) = self.renderer.size();
+
+        let captured = self.pacer.wait();
+
+        Ok(Some(Frame { width, height, luma, captured: Some(captured), truth }))
+    }
+}
+
+fn pose_at(tag: &SceneTag, time: f64) -> Mount {
+    let (Some(first), Some(last)) = (tag.path.first(), tag.path.last()) else {
+        return tag.pose;
+    };
+
+    let time =
+        if tag.looping && last.time > first.time {
+            first.time + (time - first.time).rem_euclid(last.time - first.time)
+        } else {
+            time
+        };
+
+    let i = tag.path.iter().position(|k| k.time > time).unwrap_or(tag.path.len());
+
+    match i {
+        0 => first.pose,
+        i if i == tag.path.len() => last.pose,
+        i => lerp(&tag.path[i - 1], &tag.path[i], time),
+    }
+}
+
+fn lerp(a: &Keyframe, b: &Keyframe, time: f64) -> Mount {
+    let t = (time - a.time) / (b.time - a.time);
+    let mix = |x: f64, y: f64| x + (y - x) * t;
+
+    let (a, b) = (a.pose, b.pose);
+
+    Mount {
+        x: mix(a.x, b.x),
+        y: mix(a.y, b.y),
+        z: mix(a.z, b.z),
+        roll: mix(a.roll, b.roll),
+        pitch: mix(a.pitch, b.pitch),
+        yaw: mix(a.yaw, b.yaw),
+    }
+}
@@ -0,0 +1,76 @@
+/// Code words for the first tag36h11 ids, covering every tag on the bundled
+/// field layouts. Bit order matches the reference `tag36h11.c`.
+const CODES: [u64; 31] = [
+    0x0d7e00984b, 0x0dda664ca7, 0x0dc4a1c821, 0x0e17b470e9,
+    0x0ef91d01b1, 0x0f429cdd73, 0x005da29225, 0x01106cba43,
+    0x0223bed79d, 0x021f51213c, 0x033eb19ca6, 0x03f76eb0f8,
+    0x0469a97414, 0x045dcfe0b0, 0x04a6465f72, 0x051801db96,
+    0x05eb946b4e, 0x068a7cc2ec, 0x06f0ba2652, 0x078765559d,
+    0x087b83d129, 0x086cc4a5c5, 0x08b64df90f, 0x09c577b611,
+    0x0a3810f2f5, 0x0af4d75b83, 0x0b59a03fef, 0x0bb1096f85,
+    0x0d1b92fc76, 0x0d0dd509d2, 0x0e2cfda160,
+];
+
+const BIT_X: [usize; 36] = [1, 2, 3, 4, 5, 2, 3, 4, 3, 6, 6, 6, 6, 6, 5, 5, 5, 4, 6, 5, 4, 3, 2, 5, 4, 3, 4, 1, 1, 1, 1, 1, 2, 2, 2, 3];
+const BIT_Y: [usize; 36] = [1, 1, 1, 1, 1, 2, 2, 2, 3, 1, 2, 3, 4, 5, 2, 3, 4, 3, 6, 6, 6, 6, 6, 5, 5, 5, 4, 6, 5, 4, 3, 2, 5, 4, 3, 4];
+
+/// Cells across the black border, which is what tag images are cropped to.
+pub const SIZE: usize = 8;
+
+pub fn ids() -> impl Iterator<Item = u32> {
+    0..CODES.len() as u32
+}
+
+/// The tag's cells row by row inside its black border, with set bits white.
+pub fn pattern(id: u32) -> Option<Vec<u8>> {
+    let code = *CODES.get(id as usize)?;
+    let mut luma = vec![0; SIZE * SIZE];
+
+    for (i, (&x, &y)) in BIT_X.iter().zip(&BIT_Y).enumerate() {
+        if code >> (35 - i) & 1 == 1 {
+            luma[y * SIZE + x] = 255;
+        }
+    }
+
+    Some(luma)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bits(luma: &[u8]) -> u64 {
+        (1..SIZE - 1)
+            .flat_map(|y| (1..SIZE - 1).map(move |x| (x, y)))
+            .fold(0, |acc, (x, y)| acc << 1 | (luma[y * SIZE + x] > 0) as u64)
+    }
+
+    fn rotate(luma: &[u8]) -> Vec<u8> {
+        (0..SIZE * SIZE)
+            .map(|i| luma[(SIZE - 1 - i % SIZE) * SIZE + i / SIZE])
+            .collect()
+    }
+
+    #[test]
+    fn patterns_keep_family_distance_under_rotation() {
+        let patterns: Vec<Vec<u8>> = ids().filter_map(pattern).collect();
+
+        for (a, pa) in patterns.iter().enumerate() {
+            let mut border = (0..SIZE).flat_map(|i| [i, i * SIZE, i * SIZE + SIZE - 1, (SIZE - 1) * SIZE + i]);
+            assert!(border.all(|i| pa[i] == 0), "tag {} border", a);
+
+            let mut rotated = pa.clone();
+            for turn in 0..4 {
+                for (b, pb) in patterns.iter().enumerate().skip(a) {
+                    if a == b && turn == 0 {
+                        continue;
+                    }
+
+                    let distance = (bits(&rotated) ^ bits(pb)).count_ones();
+                    assert!(distance >= 11, "tags {} and {} differ by {} bits", a, b, distance);
+                }
+                rotated = rotate(&rotated);
+            }
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use super::{Frame, FrameSource, Pacer};
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Result};
+
+/// Plays back the luma plane of an uncompressed YUV4MPEG2 (`.y4m`) file.
+pub struct VideoSource {
+    reader: BufReader<File>,
+    start: u64,
+    width: u32,
+    height: u32,
+    chroma: usize,
+    looping: bool,
+    pacer: Pacer,
+}
+
+impl VideoSource {
+    pub fn open(path: &Path, fps: Option<f64>, looping: bool) -> Result<Self> {
+        let mut reader = BufReader::new(File::open(path).map_err(|e| anyhow!("{}: {}", path.display(), e))?);
+
+        let mut header = String::new();
+        reader.read_line(&mut header)?;
+
+        let mut params = header.split_whitespace();
+        if params.next() != Some("YUV4MPEG2") {
+            bail!("{}: not a y4m file", path.display());
+        }
+
+        let (mut width, mut height, mut rate, mut colorspace) = (0, 0, 30.0, "420");
+
+        for param in params {
+            let (key, val) = param.split_at(1);
+
+            match key {
+                "W" => width = val.parse()?,
+                "H" => height = val.parse()?,
+                "F" => {
+                    if let Some((n, d)) = val.split_once(':') {
+                        let (n, d): (f64, f64) = (n.parse()?, d.parse()?);
+                        if d > 0.0 { rate = n / d; }
+                    }
+                }
+                "C" => colorspace = val,
+                _ => {}
+            }
+        }
+
+        if width == 0 || height == 0 {
+            bail!("{}: missing frame size", path.display());
+        }
+
+        let (cw, ch) = ((width as usize).div_ceil(2), (height as usize).div_ceil(2));
+        let chroma =
+            match colorspace {
+                c if c.starts_with("mono") => 0,
+                c if c.starts_with("420") => 2 * cw * ch,
+                c if c.starts_with("422") => 2 * cw * height as usize,
+                c if c.starts_with("444") => 2 * (width * height) as usize,
+                c => bail!("{}: unsupported colorspace {}", path.display(), c),
+            };
+
+        let start = reader.stream_position()?;
+
+        Ok(Self {
+            reader,
+            start,
+            width,
+            height,
+            chroma,
+            looping,
+            pacer: Pacer::new(fps.unwrap_or(rate)),
+        })
+    }
+
+    fn read(&mut self) -> Result<Option<Vec<u8>>> {
+        let mut marker = String::new();
+        if self.reader.read_line(&mut marker)? == 0 {
+            return Ok(None);
+        }
+        if !marker.starts_with("FRAME") {
+            bail!("corrupt frame header");
+        }
+
+        let mut luma = vec![0; (self.width * self.height) as usize];
+        self.reader.read_exact(&mut luma)?;
+        self.reader.seek_relative(self.chroma as i64)?;
+
+        Ok(Some(luma))
+    }
+}
+
+impl FrameSource for VideoSource {
+    fn frame(&mut self) -> Result<Option<Frame>> {
+        let luma = match self.read()? {
+            Some(luma) => luma,
+            None if self.looping => {
+                self.reader.seek(SeekFrom::Start(self.start))?;
+                self.read()?.ok_or_else(|| anyhow!("video has no frames"))?
+            }
+            None => return Ok(None),
+        };
+
+        let captured = self.pacer.wait();
+
+        Ok(Some(Frame { width: self.width, height: self.height, luma, captured: Some(captured), truth: Vec::new() }))
+    }
+}
@@ -1,20 +1,32 @@
-use crate::{config::Config, meta::Meta};
+use crate::{config::{Config, Filter, RecordConfig, ServerConfig, Source}, meta::Meta};
+use crate::calib::{Calibration, Session};
 use crate::data::{self, Data};
+use crate::field::Layout;
+use crate::geom::Intrinsics;
+use crate::nt::{Clock, Topics};
+use crate::record::Recorder;
 
 use std::ops::Index;
 use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::atomic::AtomicBool;
 use std::thread;
 
+use anyhow::{bail, Result};
 use tokio::sync::Notify;
 
 pub struct States {
     pub states: Vec<Arc<State>>,
     pub meta: Meta,
     pub notify: Arc<Notify>,
+    pub topics: Arc<Topics>,
+    pub clock: Arc<Clock>,
+    pub field: Arc<Mutex<Layout>>,
+    pub calibrations: Arc<Mutex<Vec<Calibration>>>,
+    pub recorder: Arc<Recorder>,
 }
 
 impl States {
-    pub fn new(n_cams: u32) -> Self {
+    pub fn new(n_cams: u32, layout: Layout, filter: Filter, record: RecordConfig) -> Self {
         let mut next_idx = 0;
 
         let configs =
@@ -28,14 +40,24 @@ impl States {
         let meta = Meta::new(n_cams, &configs);
 
         let notify = Arc::new(Notify::new());
+        let topics = Arc::new(Topics::default());
+        let clock = Arc::new(Clock::default());
+        let field = Arc::new(Mutex::new(layout));
+        let calibrations = Arc::new(Mutex::new(Calibration::load_all().unwrap_or_default()));
+        let recorder = Arc::new(Recorder::new(record));
 
         let states: Vec<_> =
             (0..n_cams)
                 .map(|idx| {
                     let state = Arc::new(State::new(
                         idx,
-                        configs[idx as usize],
+                        configs[idx as usize].clone(),
                         notify.clone(),
+                        topics.clone(),
+                        field.clone(),
+                        filter.clone(),
+                        calibrations.clone(),
+                        recorder.clone(),
                     ));
 
                     let st = state.clone();
@@ -45,7 +67,26 @@ impl States {
                 })
                 .collect();
 
-        States { states, meta, notify }
+        States { states, meta, notify, topics, clock, field, calibrations, recorder }
+    }
+
+    /// Names become NT topic paths, so they must be unique and free of '/'.
+    pub fn check_name(&self, id: usize, server: &ServerConfig) -> Result<()> {
+        let name = server.name();
+
+        if name.contains('/') {
+            bail!("camera name {:?} can't contain '/'", name);
+        }
+
+        let taken = self.states.iter()
+            .enumerate()
+            .any(|(idx, st)| idx != id && st.name() == name);
+
+        if taken {
+            bail!("camera name {:?} is already in use", name);
+        }
+
+        Ok(())
     }
 }
 
@@ -63,16 +104,42 @@ pub struct State {
     config: Mutex<Config>,
     pub notify: Arc<Notify>,
     pub all_notify: Arc<Notify>,
+    pub topics: Arc<Topics>,
+    pub field: Arc<Mutex<Layout>>,
+    pub filter: Filter,
+    pub calibrations: Arc<Mutex<Vec<Calibration>>>,
+    pub recorder: Arc<Recorder>,
+    pub calib: Mutex<Option<Session>>,
+    pub snapshot: AtomicBool,
+    pub capture: AtomicBool,
 }
 
 impl State {
-    pub fn new(id: u32, config: Config, all_notify: Arc<Notify>) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        id: u32,
+        config: Config,
+        all_notify: Arc<Notify>,
+        topics: Arc<Topics>,
+        field: Arc<Mutex<Layout>>,
+        filter: Filter,
+        calibrations: Arc<Mutex<Vec<Calibration>>>,
+        recorder: Arc<Recorder>,
+    ) -> Self {
         Self {
             id,
             all_notify,
+            topics,
+            field,
+            filter,
+            calibrations,
+            recorder,
+            calib: None.into(),
             config: config.into(),
             data: Data::default().into(),
             notify: Notify::new().into(),
+            snapshot: AtomicBool::new(false),
+            capture: AtomicBool::new(false),
         }
     }
 
@@ -83,4 +150,46 @@ impl State {
     pub fn config(&self) -> MutexGuard<'_, Config> {
         self.config.lock().unwrap()
     }
+
+    pub fn field(&self) -> MutexGuard<'_, Layout> {
+        self.field.lock().unwrap()
+    }
+
+    pub fn calib(&self) -> MutexGuard<'_, Option<Session>> {
+        self.calib.lock().unwrap()
+    }
+
+    /// Calibrations belong to physical cameras, so other sources never use one.
+    pub fn calibration(&self) -> Option<Calibration> {
+        let (camera, res) = {
+            let config = self.config();
+
+            if config.server.source != Source::Camera {
+                return None;
+            }
+            (config.server.camera, config.server.res)
+        };
+
+        self.calibrations
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|c| c.camera == camera && c.res == res)
+            .cloned()
+    }
+
+    pub fn intrinsics(&self) -> Intrinsics {
+        let (res, fov) = {
+            let config = self.config();
+            (config.server.res, config.detector.fov as f64)
+        };
+
+        self.calibration()
+            .map(|c| c.intrinsics())
+            .unwrap_or_else(|| Intrinsics::from_fov(res.0, res.1, fov))
+    }
+
+    pub fn name(&self) -> String {
+        self.config().server.name()
+    }
 }
@@ -0,0 +1,274 @@
+use crate::config::TrackConfig;
+use crate::data::CameraTag;
+use crate::geom::{Rotation, Transform, Translation};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct TrackInfo {
+    pub id: u64,
+    pub hits: u32,
+    pub age: f64,
+    pub velocity: Translation,
+}
+
+#[derive(Default)]
+pub struct Tracker {
+    tracks: Vec<Track>,
+    next_id: u64,
+}
+
+struct Track {
+    id: u64,
+    tag_id: Option<u32>,
+    axes: [Axis; 3],
+    rotation: Rotation,
+    hits: u32,
+    misses: u32,
+    created: f64,
+    predicted: f64,
+}
+
+/// Constant-velocity Kalman filter over one translation axis.
+#[derive(Clone, Copy)]
+struct Axis {
+    p: f64,
+    v: f64,
+    cov: [[f64; 2]; 2],
+}
+
+impl Tracker {
+    /// Associates this frame's detections with existing tracks and returns
+    /// the smoothed tags of tracks that have been confirmed.
+    pub fn update(&mut self, config: &TrackConfig, mount: Transform, time: f64, tags: &[CameraTag]) -> Vec<CameraTag> {
+        for track in &mut self.tracks {
+            track.predict(config, time);
+        }
+
+        let mut matched = vec![false; self.tracks.len()];
+        let mut out = Vec::new();
+
+        for tag in tags {
+            let pos = tag.poses.best.translation;
+
+            let nearest =
+                self.tracks
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, t)| !matched[*i] && t.tag_id == tag.tag.id)
+                    .map(|(i, t)| (i, (t.position() - pos).norm()))
+                    .filter(|(_, d)| *d < config.gate)
+                    .min_by(|a, b| a.1.total_cmp(&b.1))
+                    .map(|(i, _)| i);
+
+            let idx = match nearest {
+                Some(i) => {
+                    self.tracks[i].correct(config, tag.poses.best);
+                    i
+                }
+                None => {
+                    self.tracks.push(Track::new(self.next_id, tag, time));
+                    self.next_id += 1;
+                    matched.push(false);
+                    self.tracks.len() - 1
+                }
+            };
+            matched[idx] = true;
+
+            let track = &self.tracks[idx];
+            if track.hits < config.min_hits {
+                continue;
+            }
+
+            let best = Transform::new(track.position(), track.rotation);
+
+            let mut tag = *tag;
+            tag.poses.best = best;
+            tag.robot = mount * best;
+            tag.track = Some(TrackInfo {
+                id: track.id,
+                hits: track.hits,
+                age: time - track.created,
+                velocity: track.velocity(),
+            });
+
+            out.push(tag);
+        }
+
+        for (track, hit) in self.tracks.iter_mut().zip(&matched) {
+            if !hit {
+                track.misses += 1;
+            }
+        }
+        // confirmed tracks coast until max_misses, tentative ones die on their first miss
+        self.tracks.retain(|t| t.misses <= config.max_misses && (t.misses == 0 || t.hits >= config.min_hits));
+
+        out
+    }
+}
+
+impl Track {
+    fn new(id: u64, tag: &CameraTag, time: f64) -> Self {
+        let Transform { translation: t, rotation } = tag.poses.best;
+
+        Self {
+            id,
+            tag_id: tag.tag.id,
+            axes: [t.x, t.y, t.z].map(Axis::new),
+            rotation,
+            hits: 1,
+            misses: 0,
+            created: time,
+            predicted: time,
+        }
+    }
+
+    fn position(&self) -> Translation {
+        let [x, y, z] = self.axes.map(|a| a.p);
+        Translation::new(x, y, z)
+    }
+
+    fn velocity(&self) -> Translation {
+        let [x, y, z] = self.axes.map(|a| a.v);
+        Translation::new(x, y, z)
+    }
+
+    /// Advances the state to `time`. Coasting tracks predict every frame,
+    /// so each step starts from the last prediction rather than the last
+    /// correction.
+    fn predict(&mut self, config: &TrackConfig, time: f64) {
+        let dt = (time - self.predicted).max(0.0);
+
+        for axis in &mut self.axes {
+            axis.predict(dt, config.process_noise);
+        }
+        self.predicted = self.predicted.max(time);
+    }
+
+    fn correct(&mut self, config: &TrackConfig, meas: Transform) {
+        let t = meas.translation;
+        for (axis, z) in self.axes.iter_mut().zip([t.x, t.y, t.z]) {
+            axis.correct(z, config.measurement_noise);
+        }
+
+        let r = meas.rotation;
+        let prev = self.rotation;
+        let s = if prev.w * r.w + prev.x * r.x + prev.y * r.y + prev.z * r.z < 0.0 { -1.0 } else { 1.0 };
+        let a = config.rotation_alpha;
+
+        self.rotation = Rotation {
+            w: prev.w * (1.0 - a) + r.w * s * a,
+            x: prev.x * (1.0 - a) + r.x * s * a,
+            y: prev.y * (1.0 - a) + r.y * s * a,
+            z: prev.z * (1.0 - a) + r.z * s * a,
+        }.normalize();
+
+        self.hits += 1;
+        self.misses = 0;
+    }
+}
+
+impl Axis {
+    fn new(p: f64) -> Self {
+        Self { p, v: 0.0, cov: [[1.0, 0.0], [0.0, 1.0]] }
+    }
+
+    fn predict(&mut self, dt: f64, q: f64) {
+        let [[a, b], [c, d]] = self.cov;
+
+        self.p += self.v * dt;
+        self.cov = [
+            [a + dt * (b + c) + dt * dt * d + q * dt.powi(4) / 4.0, b + dt * d + q * dt.powi(3) / 2.0],
+            [c + dt * d + q * dt.powi(3) / 2.0, d + q * dt * dt],
+        ];
+    }
+
+    fn correct(&mut self, z: f64, r: f64) {
+        let [[a, b], [c, d]] = self.cov;
+
+        let s = a + r;
+        let (k0, k1) = (a / s, c / s);
+        let y = z - self.p;
+
+        self.p += k0 * y;
+        self.v += k1 * y;
+        self.cov = [
+            [(1.0 - k0) * a, (1.0 - k0) * b],
+            [c - k1 * a, d - k1 * b],
+        ];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::solve::TagPoses;
+
+    use serde_json::json;
+
+    fn tag(time: f64, x: f64) -> CameraTag {
+        let best = Transform::new(Translation::new(x, 0.5, 0.2), Rotation::default());
+
+        CameraTag {
+            camera: 0,
+            time,
+            robot: best,
+            poses: TagPoses { best, alt: best, ..Default::default() },
+            track: None,
+            tag: serde_json::from_value(json!({
+                "id": 3,
+                "corners": [[0.0, 0.0], [0.0, 0.0], [0.0, 0.0], [0.0, 0.0]],
+                "rot": 0.0,
+                "pos": [0.0, 0.0, 0.0],
+            })).unwrap(),
+        }
+    }
+
+    #[test]
+    fn coasts_through_gaps_at_constant_velocity() {
+        let config = TrackConfig { max_misses: 10, ..Default::default() };
+        let mut tracker = Tracker::default();
+
+        let (v, dt) = (2.0, 0.05);
+        let mut last = None;
+
+        for i in 0..40 {
+            let time = i as f64 * dt;
+            let x = 1.0 + v * time;
+
+            // drop frames 15..21 so the track has to coast
+            let seen = if (15..21).contains(&i) { vec![] } else { vec![tag(time, x)] };
+            let out = tracker.update(&config, Transform::default(), time, &seen);
+
+            if let Some(t) = out.first() {
+                let info = t.track.unwrap();
+
+                assert_eq!(*last.get_or_insert(info.id), info.id, "track lost at frame {}", i);
+
+                if i > 10 {
+                    assert!((t.poses.best.translation.x - x).abs() < 0.02, "position off at frame {}", i);
+                    assert!((info.velocity.x - v).abs() < 0.2, "velocity off at frame {}", i);
+                }
+            }
+        }
+
+        assert_eq!(tracker.tracks.len(), 1);
+        assert!((tracker.tracks[0].velocity().x - v).abs() < 0.05);
+        assert!(tracker.tracks[0].velocity().y.abs() < 0.05);
+    }
+
+    #[test]
+    fn stays_confirmed_through_a_dropped_detection() {
+        let config = TrackConfig { min_hits: 3, ..Default::default() };
+        let mut tracker = Tracker::default();
+
+        let mut seen = |time: f64, tags: &[CameraTag]| tracker.update(&config, Transform::default(), time, tags).len();
+
+        assert_eq!(seen(0.0, &[tag(0.0, 1.0)]), 0);
+        assert_eq!(seen(0.05, &[tag(0.05, 1.0)]), 0);
+        assert_eq!(seen(0.1, &[tag(0.1, 1.0)]), 1);
+        assert_eq!(seen(0.15, &[]), 0);
+        assert_eq!(seen(0.2, &[tag(0.2, 1.0)]), 1, "track hidden after one dropped frame");
+    }
+}
@@ -1,25 +1,36 @@
+use crate::calib::{Board, Calibration, Session};
 use crate::config::Config;
+use crate::data::CameraTag;
+use crate::field::Layout;
 use crate::meta::Meta;
+use crate::nt::{Sample, Topic};
+use crate::nt::mock::{self, MockServer, MockTopic};
+use crate::record::Status;
 use crate::state::States;
 
-use dauntless::Tag;
 use rocket::futures::SinkExt;
 use rocket_ws::{Channel, WebSocket};
 
 use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
 
 use colored::Colorize;
 use rust_embed::Embed;
-use serde_json::json;
+use serde::Deserialize;
+use serde_json::{json, Value};
 
 use rocket::{Build, Request, Rocket, State as RState};
 use rocket::fairing::AdHoc;
 use rocket::http::ContentType;
+use rocket::response::status::BadRequest;
+use rocket::tokio::{task, time};
 use rocket::serde::json::Json;
 
-pub fn build(states: States) -> Rocket<Build> {
+pub fn build(states: States, mock: Option<MockServer>) -> Rocket<Build> {
     rocket::build()
         .manage(states)
+        .manage(mock)
         .attach(AdHoc::on_liftoff(
             "log",
             |_| Box::pin(async move {
@@ -36,6 +47,21 @@ pub fn build(states: States) -> Rocket<Build> {
             mask,
             get_config,
             set_config,
+            topics,
+            topic,
+            clock,
+            get_field,
+            set_field,
+            get_calibration,
+            start_calibration,
+            capture_calibration,
+            solve_calibration,
+            truth,
+            get_record,
+            start_record,
+            stop_record,
+            mock_topics,
+            mock_inject,
         ])
 }
 
@@ -81,10 +107,25 @@ fn data(id: usize, states: &RState<States>, ws: WebSocket) -> Channel<'static> {
             let msg = {
                 let data = state.data();
 
-                let mut tags: Vec<Tag> = data.tags.iter().map(|t| t.tag).collect();
-                tags.sort_by_key(|t| t.id);
+                let mut tags: Vec<(&CameraTag, bool)> =
+                    data.tags
+                        .iter()
+                        .map(|t| (t, false))
+                        .chain(data.rejected.iter().map(|t| (t, true)))
+                        .collect();
+                tags.sort_by_key(|(t, _)| t.tag.id);
 
-                let json = json!({ "ms": data.ms, "tags": tags });
+                let tags: Vec<Value> =
+                    tags
+                        .into_iter()
+                        .map(|(t, rejected)| {
+                            let mut value = serde_json::to_value(t).unwrap();
+                            value["rejected"] = rejected.into();
+                            value
+                        })
+                        .collect();
+
+                let json = json!({ "ms": data.ms, "latency": data.latency, "tags": tags, "pose": data.pose });
                 serde_json::to_string(&json).unwrap()
             };
 
@@ -99,8 +140,11 @@ fn frame(id: usize, states: &RState<States>, ws: WebSocket) -> Channel<'static>
 
     ws.channel(move |mut stream| Box::pin(async move {
         loop {
-            let frame = state.data().frame.clone().unwrap();
-            stream.send(frame.into()).await?;
+            // a source that fails to open never produces one
+            let frame = state.data().frame.clone();
+            if let Some(frame) = frame {
+                stream.send(frame.into()).await?;
+            }
 
             state.notify.notified().await;
         }
@@ -113,8 +157,10 @@ fn mask(id: usize, states: &RState<States>, ws: WebSocket) -> Channel<'static> {
 
     ws.channel(move |mut stream| Box::pin(async move {
         loop {
-            let mask = state.data().mask.clone().unwrap();
-            stream.send(mask.into()).await?;
+            let mask = state.data().mask.clone();
+            if let Some(mask) = mask {
+                stream.send(mask.into()).await?;
+            }
 
             state.notify.notified().await;
         }
@@ -124,21 +170,217 @@ fn mask(id: usize, states: &RState<States>, ws: WebSocket) -> Channel<'static> {
 #[get("/api/<id>/config")]
 fn get_config(id: usize, states: &RState<States>) -> Json<Config> {
     let config = states[id].config();
-    Json(*config)
+    Json(config.clone())
 }
 
 #[post("/api/<id>/config", data = "<config>")]
-fn set_config(id: usize, states: &RState<States>, config: Json<Config>) {
+fn set_config(id: usize, states: &RState<States>, config: Json<Config>) -> Result<(), BadRequest<String>> {
     let mut cfg = config;
     cfg.server.scale = u32::max(cfg.server.scale, 1);
 
-    *states[id].config() = *cfg;
+    states.check_name(id, &cfg.server).map_err(|e| BadRequest(e.to_string()))?;
+
+    *states[id].config() = cfg.into_inner();
 
-    let configs = states.states.iter().map(|s| *s.config()).collect();
+    let configs = states.states.iter().map(|s| s.config().clone()).collect();
     Config::save_all(configs);
+
+    Ok(())
+}
+
+#[get("/api/field")]
+fn get_field(states: &RState<States>) -> Json<Layout> {
+    Json(states.field.lock().unwrap().clone())
+}
+
+#[post("/api/field", data = "<layout>")]
+fn set_field(states: &RState<States>, layout: Json<Layout>) {
+    let layout = layout.into_inner();
+
+    if let Err(err) = layout.save_custom() {
+        println!("\rweb: {} [reason: {}]", "field save failed".red(), err);
+    }
+
+    *states.field.lock().unwrap() = layout;
+}
+
+#[get("/api/<id>/calibration")]
+fn get_calibration(id: usize, states: &RState<States>) -> Json<Value> {
+    let state = &states[id];
+    Json(json!({ "session": *state.calib(), "calibration": state.calibration() }))
+}
+
+#[post("/api/<id>/calibration/start", data = "<board>")]
+fn start_calibration(id: usize, states: &RState<States>, board: Json<Board>) -> Result<Json<Session>, BadRequest<String>> {
+    board.validate().map_err(|e| BadRequest(e.to_string()))?;
+
+    let state = &states[id];
+    let session = Session::new(board.into_inner(), state.config().server.res);
+
+    *state.calib() = Some(session.clone());
+    Ok(Json(session))
+}
+
+#[post("/api/<id>/calibration/capture")]
+async fn capture_calibration(id: usize, states: &RState<States>) -> Result<Json<Session>, BadRequest<String>> {
+    let state = states[id].clone();
+
+    if state.calib().is_none() {
+        return Err(BadRequest("no calibration session".into()));
+    }
+
+    state.capture.store(true, Ordering::Relaxed);
+
+    let captured = time::timeout(Duration::from_secs(3), async {
+        while state.capture.load(Ordering::Relaxed) {
+            state.notify.notified().await;
+        }
+    }).await;
+
+    if captured.is_err() {
+        state.capture.store(false, Ordering::Relaxed);
+        return Err(BadRequest("timed out waiting for a frame".into()));
+    }
+
+    let session = state.calib().clone();
+    session.map(Json).ok_or_else(|| BadRequest("no calibration session".into()))
+}
+
+#[post("/api/<id>/calibration/solve")]
+async fn solve_calibration(id: usize, states: &RState<States>) -> Result<Json<Calibration>, BadRequest<String>> {
+    let state = states[id].clone();
+    let camera = state.config().server.camera;
+
+    let Some(mut session) = state.calib().clone() else {
+        return Err(BadRequest("no calibration session".into()));
+    };
+
+    let (session, res) =
+        task::spawn_blocking(move || {
+            let res = session.solve(camera);
+            (session, res)
+        })
+        .await
+        .map_err(|e| BadRequest(e.to_string()))?;
+
+    let calibration = res.map_err(|e| BadRequest(e.to_string()))?;
+    *state.calib() = Some(session);
+
+    let mut calibrations = states.calibrations.lock().unwrap();
+    calibrations.retain(|c| c.camera != calibration.camera || c.res != calibration.res);
+    calibrations.push(calibration.clone());
+
+    if let Err(err) = Calibration::save_all(&calibrations) {
+        println!("\rweb: {} [reason: {}]", "calibration save failed".red(), err);
+    }
+
+    Ok(Json(calibration))
+}
+
+/// Ground truth for the latest synthetic frame, with the matching
+/// detection's pose and corner errors where the tag was found.
+#[get("/api/<id>/truth")]
+fn truth(id: usize, states: &RState<States>) -> Json<Value> {
+    let data = states[id].data();
+
+    let tags: Vec<Value> =
+        data.truth
+            .iter()
+            .map(|t| {
+                let detected =
+                    data.tags
+                        .iter()
+                        .chain(&data.rejected)
+                        .find(|d| d.tag.id == Some(t.id))
+                        .map(|d| {
+                            let pose = d.poses.best;
+                            let rot = t.pose.rotation.inverse() * pose.rotation;
+
+                            let corners = t.corners.map(|corners| {
+                                let sq: f64 =
+                                    corners
+                                        .iter()
+                                        .zip(d.tag.corners)
+                                        .map(|((u, v), (x, y))| (u - x as f64).powi(2) + (v - y as f64).powi(2))
+                                        .sum();
+                                (sq / 4.0).sqrt()
+                            });
+
+                            json!({
+                                "pose": pose,
+                                "corners": d.tag.corners,
+                                "translation_error": (pose.translation - t.pose.translation).norm(),
+                                "rotation_error": (2.0 * rot.w.abs().min(1.0).acos()).to_degrees(),
+                                "corner_error": corners,
+                            })
+                        });
+
+                json!({ "truth": t, "detected": detected })
+            })
+            .collect();
+
+    Json(json!({ "time": data.time, "tags": tags }))
+}
+
+#[get("/api/record")]
+fn get_record(states: &RState<States>) -> Json<Status> {
+    Json(states.recorder.status().clone())
+}
+
+#[post("/api/record/start")]
+fn start_record(states: &RState<States>) -> Result<Json<Status>, BadRequest<String>> {
+    states.recorder.start().map_err(|e| BadRequest(e.to_string()))?;
+    Ok(Json(states.recorder.status().clone()))
+}
+
+#[post("/api/record/stop")]
+fn stop_record(states: &RState<States>) -> Json<Status> {
+    states.recorder.stop();
+    Json(states.recorder.status().clone())
+}
+
+#[get("/api/mock")]
+fn mock_topics(mock: &RState<Option<MockServer>>) -> Option<Json<Vec<MockTopic>>> {
+    mock.as_ref().map(|m| Json(m.topics()))
+}
+
+#[derive(Deserialize)]
+struct Inject {
+    name: String,
+    #[serde(rename = "type")]
+    ty: String,
+    value: Value,
+}
+
+/// Publishes a robot-side topic on the mock NT server.
+#[post("/api/mock", data = "<inject>")]
+fn mock_inject(mock: &RState<Option<MockServer>>, inject: Json<Inject>) -> Result<(), BadRequest<String>> {
+    let mock = mock.as_ref().ok_or_else(|| BadRequest("mock nt is not running".into()))?;
+
+    let Inject { name, ty, value } = inject.into_inner();
+    let value = mock::parse_value(&ty, value).map_err(|e| BadRequest(e.to_string()))?;
+
+    mock.inject(&name, &ty, value);
+    Ok(())
 }
 
 #[get("/api/meta")]
 fn meta(state: &RState<States>) -> Json<Meta> {
     Json(state.meta.clone())
 }
+
+#[get("/api/topics")]
+fn topics(state: &RState<States>) -> Json<Vec<Topic>> {
+    Json(state.topics.all())
+}
+
+/// Looks up a single topic by its full name, e.g. `/api/topic?name=/robot/pose`.
+#[get("/api/topic?<name>")]
+fn topic(name: &str, state: &RState<States>) -> Option<Json<Topic>> {
+    state.topics.get(name).map(Json)
+}
+
+#[get("/api/clock")]
+fn clock(state: &RState<States>) -> Json<Option<Sample>> {
+    Json(state.clock.best())
+}
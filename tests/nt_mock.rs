@@ -0,0 +1,141 @@
+use dauntless_srv::calib::Calibration;
+use dauntless_srv::config::{Config, Filter, Settings, Sim, Source};
+use dauntless_srv::field::Layout;
+use dauntless_srv::nt::{self, Clock, Topics, Value};
+use dauntless_srv::nt::mock::MockServer;
+use dauntless_srv::record::Recorder;
+use dauntless_srv::state::State;
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde_json::json;
+use tokio::sync::Notify;
+use tokio::time;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+fn camera(name: &str) -> Config {
+    serde_json::from_value(json!({
+        "detector": dauntless::Config::default(),
+        "server": { "name": name, "camera": 0, "res": [640, 480], "scale": 8 },
+    }))
+    .unwrap()
+}
+
+async fn start() -> (MockServer, Arc<State>, Arc<Notify>) {
+    let server = MockServer::bind(("127.0.0.1", 0)).await.unwrap();
+
+    let mut settings = Settings::default();
+    settings.nt.hosts = vec!["127.0.0.1".into()];
+    settings.nt.port = server.addr().port();
+
+    let notify = Arc::new(Notify::new());
+    let topics = Arc::new(Topics::default());
+    let recorder = Arc::new(Recorder::new(Default::default()));
+
+    let state = Arc::new(State::new(
+        0,
+        camera("front"),
+        notify.clone(),
+        topics.clone(),
+        Arc::new(Mutex::new(Layout::default())),
+        Filter::default(),
+        Arc::new(Mutex::new(Vec::<Calibration>::new())),
+        recorder.clone(),
+    ));
+
+    tokio::spawn(nt::run(
+        settings,
+        vec![state.clone()],
+        topics,
+        Arc::new(Clock::default()),
+        notify.clone(),
+        recorder,
+    ));
+
+    (server, state, notify)
+}
+
+#[tokio::test]
+async fn publishes_camera_topics() {
+    let (server, _state, _notify) = start().await;
+
+    let connected = time::timeout(TIMEOUT, server.wait_for("/dauntless/front/connected")).await.unwrap();
+    assert_eq!(connected, Value::Bool(false));
+
+    let types = [
+        ("/dauntless/tags", "json"),
+        ("/dauntless/ids", "int[]"),
+        ("/dauntless/recording", "boolean"),
+        ("/dauntless/front/tags", "struct:Transform3d[]"),
+        ("/dauntless/front/ids", "int[]"),
+        ("/dauntless/front/latency", "double"),
+        ("/dauntless/front/seq", "int"),
+        ("/dauntless/front/config", "json"),
+        ("/dauntless/front/pipeline", "int"),
+        ("/dauntless/front/enabled", "boolean"),
+    ];
+
+    for (name, ty) in types {
+        let topic = server.topic(name).unwrap_or_else(|| panic!("{} not published", name));
+        assert_eq!(topic.ty, ty, "{}", name);
+    }
+
+    assert_eq!(server.value("/dauntless/front/ids"), Some(Value::IntList(vec![])));
+    assert_eq!(server.value("/dauntless/front/enabled"), Some(Value::Bool(true)));
+}
+
+#[tokio::test]
+async fn applies_injected_control() {
+    let (server, state, notify) = start().await;
+
+    time::timeout(TIMEOUT, server.wait_for("/dauntless/front/enabled")).await.unwrap();
+    server.inject("/dauntless/front/stray", "boolean", Value::Bool(true));
+    server.inject("/dauntless/front/control/enable", "boolean", Value::Bool(false));
+
+    time::timeout(TIMEOUT, async {
+        while state.config().server.enabled {
+            notify.notify_waiters();
+            time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .expect("control topic not applied");
+
+    time::timeout(TIMEOUT, async {
+        while server.value("/dauntless/front/enabled") != Some(Value::Bool(false)) {
+            notify.notify_waiters();
+            time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .expect("enabled state not published");
+
+    // only control topics are subscribed, not everything under the root
+    assert!(state.topics.get("/dauntless/front/stray").is_none());
+
+    server.clear();
+    assert!(server.topics().iter().all(|t| t.values.len() <= 1));
+}
+
+#[tokio::test]
+async fn subscribes_when_switched_to_sim() {
+    let (server, state, _notify) = start().await;
+
+    time::timeout(TIMEOUT, server.wait_for("/dauntless/front/enabled")).await.unwrap();
+
+    let sim = Sim::default();
+    let topic = sim.topic.clone();
+    state.config().server.source = Source::Sim(sim);
+
+    server.inject(&topic, "double[]", Value::DoubleList(vec![1.0, 2.0, 90.0]));
+
+    time::timeout(TIMEOUT, async {
+        while state.topics.get(&topic).and_then(|t| t.value).is_none() {
+            time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .expect("robot pose not received");
+}
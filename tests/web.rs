@@ -0,0 +1,25 @@
+use dauntless_srv::config::{Filter, RecordConfig};
+use dauntless_srv::field::Layout;
+use dauntless_srv::nt::mock::MockServer;
+use dauntless_srv::state::States;
+use dauntless_srv::web;
+
+fn states() -> States {
+    States::new(0, Layout::default(), Filter::default(), RecordConfig::default())
+}
+
+#[tokio::test]
+async fn ignites_without_mock() {
+    if let Err(err) = web::build(states(), None).ignite().await {
+        panic!("rocket failed to ignite: {}", err);
+    }
+}
+
+#[tokio::test]
+async fn ignites_with_mock() {
+    let mock = MockServer::bind(("127.0.0.1", 0)).await.unwrap();
+
+    if let Err(err) = web::build(states(), Some(mock)).ignite().await {
+        panic!("rocket failed to ignite: {}", err);
+    }
+}
@@ -9,7 +9,7 @@ class CameraSettings extends Component {
   sliderRef = React.createRef();
 
   render() {
-    const { meta, config: { detector, server }, update } = this.context;
+    const { meta, config: { detector, server, mount }, update } = this.context;
 
     const camRes = meta.cams[server.camera][1];
     const curRes = camRes[Math.floor(this.sliderRef.current?.value / 100 * 0.99 * camRes.length)];
@@ -19,6 +19,16 @@ class CameraSettings extends Component {
       show={this.props.show}
       onCancel={this.props.onCancel}
     >
+      <div>
+        <label htmlFor="name">Name</label>
+        <input
+          type="text"
+          name="name"
+          defaultValue={server.name}
+          onChange={(e) => update('server', { name: e.target.value })}
+        />
+      </div>
+
       <div>
         <label htmlFor="camera">Camera</label>
         <select
@@ -66,6 +76,19 @@ class CameraSettings extends Component {
         </div>
       </div>
 
+      <div>
+        <label htmlFor="undistort">Undistort</label>
+        <select
+          style={{ width: 250 }}
+          value={server.undistort ?? 'off'}
+          onChange={(e) => update('server', { undistort: e.target.value })}
+        >
+          <option value="off">Off</option>
+          <option value="frame">Frame</option>
+          <option value="corners">Corners</option>
+        </select>
+      </div>
+
       <div>
         <label htmlFor="scale">Preview Scale</label>
         <input
@@ -75,6 +98,25 @@ class CameraSettings extends Component {
           onChange={(e) => update('server', { scale: +e.target.value })}
         />
       </div>
+
+      {[
+        ['x', 'Mount X (m)'],
+        ['y', 'Mount Y (m)'],
+        ['z', 'Mount Z (m)'],
+        ['roll', 'Mount Roll (°)'],
+        ['pitch', 'Mount Pitch (°)'],
+        ['yaw', 'Mount Yaw (°)'],
+      ].map(([key, label]) => (
+        <div key={key}>
+          <label htmlFor={`mount-${key}`}>{label}</label>
+          <input
+            type="number"
+            name={`mount-${key}`}
+            defaultValue={mount?.[key] ?? 0}
+            onChange={(e) => update('mount', { [key]: +e.target.value })}
+          />
+        </div>
+      ))}
     </Popup>;
   }
 }
@@ -22,12 +22,12 @@ class Detections extends Component {
         {valid.length === 0
           ? <i>{'<no tags>'}</i>
           : valid.map((tag, i) => {
-            const { id, rot, pos } = tag;
+            const { id, rot, pos, rejected } = tag;
 
-            return <div key={`tag${i}`}>
+            return <div key={`tag${i}`} style={rejected ? { opacity: 0.5 } : {}}>
               {i !== 0 && <hr />}
 
-              <h4>Tag {id}</h4>
+              <h4>Tag {id}{rejected && ' (rejected)'}</h4>
               <p>
                 Rot: {rot.toFixed(2)}
                 <br />
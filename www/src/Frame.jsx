@@ -76,9 +76,9 @@ class Frame extends Canvas {
 
   draw = () => {
     for (const tag of this.context.data.tags) {
-      const { id, corners } = tag;
+      const { id, corners, rejected } = tag;
 
-      const clr = this.color('#ff0000', id);
+      const clr = this.color(rejected ? '#808080' : '#ff0000', id);
       const [tl, tr, bl, br] = corners;
 
       this.line(tl, tr, clr);
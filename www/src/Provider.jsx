@@ -107,10 +107,15 @@ class Provider extends Component {
   };
 
   setConfig = async () => {
-    await fetch(`/api/${this.state.id}/config`, {
+    const res = await fetch(`/api/${this.state.id}/config`, {
       method: 'POST',
       body: JSON.stringify(this.state.config),
     });
+
+    // rejected configs (e.g. a duplicate name) revert to the server's copy
+    if (!res.ok) {
+      await this.fetchConfig();
+    }
   };
 
   render() {
@@ -15,9 +15,9 @@ class Tags extends Canvas {
     this.rect(w/2 - 50, h - 25, 100, 50, '#333333');
 
     for (const tag of this.context.data.tags) {
-      const { id, rot, pos } = tag;
+      const { id, rot, pos, rejected } = tag;
 
-      if (id === null) continue;
+      if (id === null || rejected) continue;
 
       const x = w/2 + pos[0] * 100;
       const y = h - 25 - pos[2] * 100;